@@ -2,9 +2,8 @@ fn main() {
     let file_contents = include_str!("../../data/alpha.md");
 
     // create directory for example output
-    match std::fs::create_dir("example_results") {
-        Err(_) => println!("Example output directory exists at ./example_results/"),
-        Ok(()) => {}
+    if std::fs::create_dir("example_results").is_err() {
+        println!("Example output directory exists at ./example_results/");
     }
 
     // do work
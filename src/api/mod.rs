@@ -1,83 +1,223 @@
-// Event, Tag, and CreateIcsEvent are part of the API
+// the event model, Tag, Inline, Filter, the document model, the ics conversion traits and their
+// configuration are part of the API
+#[cfg(feature = "serde")]
+pub use crate::format::JsonWriter;
 pub use crate::format::{
-    alpha::{Event, Tag},
-    CreateIcsEvent,
+    agenda::{agenda, Occurrence},
+    alpha::{
+        writer::{format_alpha, AlphaWriter},
+        Tag,
+    },
+    diff::{diff, Change},
+    document::{Block, Document, Item},
+    inline::Inline,
+    lint::{check_alpha, check_calendar, Diagnostic, Lint, Severity},
+    parse_offset, CreateIcsEvent, CreateIcsTodo, DateContext, DateVariant, Event, Filter,
+    IcsConfig, IcsWriter, Parser, Recurrence, Registry, Source, SourceFormat, Span, TodoPolicy,
+    Writer, YearPolicy,
 };
 
-use ics::{ICalendar, TimeZone, ZoneTime};
+use crate::format::ical::{add_events, map_categories};
+use ics::components::Property;
+use ics::ICalendar;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
 use std::path::Path;
 
-pub enum SourceFormat {
-    Alpha,
-}
+/// Names the calendars an event belongs to.
+pub type PartitionFn = Box<dyn Fn(&Event) -> Vec<String>>;
 
-pub fn save_as_ics<P>(format: SourceFormat, year: i32, source: &str, destination: P)
-where
-    P: AsRef<Path>,
-{
-    match format {
-        SourceFormat::Alpha => {
-            save_alpha_as_ics(year, source, destination);
-        }
-    }
+/// How to split the events into separate calendars. An event can end up in several calendars,
+/// and events that do not belong to any partition go to a calendar named after the format.
+pub enum Partition {
+    /// One calendar per tag, named by the tag's category name.
+    ByTag,
+    /// One calendar per section or year block.
+    BySection,
+    /// One calendar per name returned by the function.
+    Custom(PartitionFn),
 }
 
-pub fn parse(format: SourceFormat, year: i32, source: &str) {
-    match format {
-        SourceFormat::Alpha => {
-            parse_alpha(year, source);
-        }
+impl Partition {
+    fn names(&self, event: &Event, config: &IcsConfig) -> Vec<String> {
+        let names = match self {
+            Partition::ByTag => event.tags.iter().map(|tag| tag.name().to_owned()).collect(),
+            Partition::BySection => event.section.iter().cloned().collect(),
+            Partition::Custom(f) => f(event),
+        };
+        // name the calendars like the categories, leaving out the ones that are not categories
+        map_categories(names, &config.category_map)
     }
 }
 
-fn save_alpha_as_ics<P>(start_year: i32, source: &str, destination: P)
+pub fn save_as_ics<F, P>(format: F, year: i32, source: &str, destination: P)
 where
+    F: Parser,
     P: AsRef<Path>,
 {
-    let events = parse_alpha(start_year, source);
-
-    let mut calendar = ICalendar::new("2.0", "alpha");
+    save_as_ics_with_config(format, year, source, destination, &IcsConfig::default());
+}
 
-    // Add Helsinki timezone
-    let tz = TimeZone::new(
-        "Europe/Helsinki",
-        // NOTE: if the law for daylight saving time changes in Finland, use ZoneTime::standard
-        ZoneTime::daylight("19671025T040000", "+0200", "+0300"),
-    );
-    calendar.add_timezone(tz);
+pub fn save_as_ics_with_config<F, P>(
+    format: F,
+    year: i32,
+    source: &str,
+    destination: P,
+    config: &IcsConfig,
+) where
+    F: Parser,
+    P: AsRef<Path>,
+{
+    let events = parse_with_filter(&format, year, source, &config.filter);
 
-    for entry in &events {
-        let event = entry.create_ics_event();
-        calendar.add_event(event);
-    }
+    let mut calendar = ICalendar::new("2.0", format.name());
+    add_events(&mut calendar, &events, config);
 
     calendar
         .save_file(destination.as_ref().to_str().unwrap())
         .unwrap();
 }
 
-fn parse_alpha(start_year: i32, source: &str) -> Vec<Event> {
-    // split into a queue of lines
-    let lines = source.split('\n');
-
-    // process lines into DOM
-    let mut events = Vec::new();
-    let mut cur_year = start_year;
-    for line in lines {
-        let event = Event::from_str(line, cur_year);
-        if event.is_ok() {
-            let event = event.unwrap();
-            if event.tags.contains(&Tag::PublishToIcs) {
-                info!("publishing event {:?} to ics", event);
-                events.push(event);
+/// Saves the events as one calendar per partition into `directory`, each named `<partition>.ics`.
+/// The directory is created if it does not exist.
+pub fn save_partitioned_as_ics<F, P>(
+    format: F,
+    year: i32,
+    source: &str,
+    partition: &Partition,
+    directory: P,
+    config: &IcsConfig,
+) -> io::Result<()>
+where
+    F: Parser,
+    P: AsRef<Path>,
+{
+    let events = parse_with_filter(&format, year, source, &config.filter);
+    save_events_partitioned_as_ics(format.name(), &events, partition, directory, config)
+}
+
+/// Saves the events like `save_partitioned_as_ics`, with `name` as the name of the calendar of
+/// the events outside the partitions and as the start of the product identifiers. Calendars
+/// whose names make the same file name, eg. `a/b` and `a_b`, are told apart by a number, as in
+/// `a_b-2.ics`.
+pub fn save_events_partitioned_as_ics<P>(
+    name: &str,
+    events: &[Event],
+    partition: &Partition,
+    directory: P,
+    config: &IcsConfig,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let mut partitions: BTreeMap<String, Vec<&Event>> = BTreeMap::new();
+    for event in events {
+        let mut names = partition.names(event, config);
+        if names.is_empty() {
+            names.push(name.to_owned());
+        }
+        for name in names {
+            partitions.entry(name).or_default().push(event);
+        }
+    }
+
+    std::fs::create_dir_all(directory.as_ref())?;
+    let mut file_names = BTreeSet::new();
+    for (partition_name, events) in partitions {
+        let calendar = build_calendar(name, &partition_name, events, config);
+        let base = file_name(&partition_name);
+        let mut file = base.clone();
+        for number in 2.. {
+            if file_names.insert(file.clone()) {
+                break;
+            }
+            file = format!("{}-{}", base, number);
+        }
+        let destination = directory.as_ref().join(format!("{}.ics", file));
+        info!(
+            "saving calendar \"{}\" to {:?}",
+            partition_name, destination
+        );
+        calendar.save_file(destination)?;
+    }
+    Ok(())
+}
+
+/// Merges the events of several files into one list, each file given by its name and its
+/// events. The events record the file they came from, and an event that is the same as an
+/// earlier one, eg. copied from one file to another, is left out.
+pub fn merge<I>(files: I) -> Vec<Event>
+where
+    I: IntoIterator<Item = (String, Vec<Event>)>,
+{
+    let mut merged: Vec<Event> = Vec::new();
+    for (file, events) in files {
+        for mut event in events {
+            let duplicate = merged.iter().find(|other| other.is_same(&event));
+            if let Some(other) = duplicate {
+                info!(
+                    "leaving out {:?} from {}, the same as in {:?}",
+                    event.description, file, other.source.file
+                );
+                continue;
             }
-        } else if let Ok(year) = line.parse::<i32>() {
-            debug!("context changes year: {}", year);
-            cur_year = year;
-        } else {
-            debug!("ignored line {}", line);
+            event.source.file = Some(file.clone());
+            merged.push(event);
         }
     }
+    merged
+}
 
-    events
+/// Makes a calendar name safe to use as a file name.
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Parses the source into a document of its events, sections, year contexts and the lines that
+/// could not be parsed.
+pub fn parse<F>(format: F, year: i32, source: &str) -> Document
+where
+    F: Parser,
+{
+    format.parse(year, source)
+}
+
+/// Parses the source like `parse`, inferring the years of dates without one from the context.
+pub fn parse_with_context<F>(format: F, context: &DateContext, source: &str) -> Document
+where
+    F: Parser,
+{
+    format.parse_with_context(context, source)
+}
+
+/// Parses the events in the source that match the filter.
+pub fn parse_with_filter<F>(format: F, year: i32, source: &str, filter: &Filter) -> Vec<Event>
+where
+    F: Parser,
+{
+    format
+        .parse(year, source)
+        .into_events()
+        .into_iter()
+        .filter(|event| filter.matches(event))
+        .collect()
+}
+
+/// Builds a calendar with its own name and product identifier.
+fn build_calendar<'a, I>(format: &str, name: &str, events: I, config: &IcsConfig) -> ICalendar<'a>
+where
+    I: IntoIterator<Item = &'a Event>,
+{
+    let mut calendar = ICalendar::new("2.0", format!("{}/{}", format, name));
+    calendar.push(Property::new("X-WR-CALNAME", name.to_owned()));
+    add_events(&mut calendar, events, config);
+    calendar
 }
@@ -0,0 +1,214 @@
+#[cfg(test)]
+mod test;
+
+use chrono::NaiveDate;
+use lsp_types::{
+    CodeAction, CodeActionKind, CompletionItem, CompletionItemKind, Diagnostic, DiagnosticSeverity,
+    Hover, HoverContents, MarkupContent, MarkupKind, NumberOrString, Position, Range, TextEdit,
+    Url, WorkspaceEdit,
+};
+use memoparsa::{Document, Event, Parser, Registry, Severity, SourceFormat, Span, Tag};
+use std::collections::HashMap;
+
+/// The built-in format of a file detected by its name in the registry, eg. `CALENDAR.md` is a
+/// calendar. The files of other formats are taken to be alpha files.
+pub fn format_of(registry: &Registry, uri: &Url) -> SourceFormat {
+    let name = registry.detect(uri.path()).map(|parser| parser.name());
+    [SourceFormat::Alpha, SourceFormat::Calendar]
+        .iter()
+        .copied()
+        .find(|format| Some(format.name()) == name)
+        .unwrap_or(SourceFormat::Alpha)
+}
+
+/// An open file with its parsed document.
+pub struct File {
+    pub format: SourceFormat,
+    pub year: i32,
+    pub text: String,
+    pub document: Document,
+}
+
+impl File {
+    pub fn new(format: SourceFormat, year: i32, text: String) -> File {
+        let document = format.parse(year, &text);
+        File {
+            format,
+            year,
+            text,
+            document,
+        }
+    }
+
+    /// The problems in the file, with events ending before `today` in the past.
+    pub fn diagnostics(&self, today: NaiveDate) -> Vec<Diagnostic> {
+        let diagnostics = match self.format {
+            SourceFormat::Alpha => memoparsa::check_alpha(self.year, &self.text, today),
+            SourceFormat::Calendar => memoparsa::check_calendar(self.year, &self.text),
+        };
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| Diagnostic {
+                range: match &diagnostic.span {
+                    Some(span) => self.range(span),
+                    None => self.line_range(diagnostic.line..diagnostic.line + 1),
+                },
+                severity: Some(match diagnostic.severity() {
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                    Severity::Error => DiagnosticSeverity::ERROR,
+                }),
+                code: Some(NumberOrString::String(format!("{:?}", diagnostic.lint))),
+                source: Some("memoparsa".to_owned()),
+                message: diagnostic.message,
+                ..Diagnostic::default()
+            })
+            .collect()
+    }
+
+    /// The resolved date of the event on the line, with its weekday, tags and recurrence.
+    pub fn hover(&self, position: Position) -> Option<Hover> {
+        let event = self.event_at(position.line as usize)?;
+        let mut value = format!("**{}**", event.date);
+        if event.approximate {
+            value.push_str(" (estimate)");
+        }
+        if !event.tags.is_empty() {
+            let tags = event.tags.iter().map(|tag| tag.name()).collect::<Vec<_>>();
+            value.push_str(&format!("\n\ntags: {}", tags.join(", ")));
+        }
+        if let Some(recurrence) = event.recurrence {
+            value.push_str(&format!("\n\nrepeats {:?}", recurrence).to_lowercase());
+        }
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: event.source.date.as_ref().map(|span| self.range(span)),
+        })
+    }
+
+    /// The tags of the legend, when the cursor is in the tag column of an alpha entry.
+    pub fn completion(&self, position: Position) -> Vec<CompletionItem> {
+        if self.format != SourceFormat::Alpha {
+            return vec![];
+        }
+        let line = match self.text.split('\n').nth(position.line as usize) {
+            Some(line) => line,
+            None => return vec![],
+        };
+        let before = &line[..byte_index(line, position.character)];
+        let (head, partial) = match before.rsplit_once(char::is_whitespace) {
+            Some(split) => split,
+            None => return vec![],
+        };
+        if !partial.chars().all(is_tag_char) {
+            return vec![];
+        }
+        // the text before the tag column must be the date of an entry
+        let year = self.document.year_at(position.line as usize);
+        match Event::from_str(&format!("{} x", head), year) {
+            Ok(event) if event.tags.is_empty() && event.description == "x" => {}
+            _ => return vec![],
+        }
+
+        Tag::ALL
+            .iter()
+            .filter(|tag| !partial.contains(tag.key()))
+            .map(|tag| CompletionItem {
+                label: tag.key().to_owned(),
+                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                detail: Some(tag.name().to_owned()),
+                ..CompletionItem::default()
+            })
+            .collect()
+    }
+
+    /// Rewriting the alpha entry at the start of the range into the canonical form of `fmt`.
+    pub fn code_actions(&self, uri: &Url, range: Range) -> Vec<CodeAction> {
+        if self.format != SourceFormat::Alpha {
+            return vec![];
+        }
+        let lines = match self
+            .document
+            .events_with_lines()
+            .find(|(_, lines)| lines.contains(&(range.start.line as usize)))
+        {
+            Some((_, lines)) => lines,
+            None => return vec![],
+        };
+
+        // formatting keeps the lines in place, so the entry is on the same lines when formatted
+        let formatted = memoparsa::format_alpha(self.document.start_year, &self.text);
+        let formatted = formatted.split('\n').collect::<Vec<&str>>();
+        let original = self.text.split('\n').collect::<Vec<&str>>();
+        if formatted.len() != original.len() || formatted[lines.clone()] == original[lines.clone()]
+        {
+            return vec![];
+        }
+
+        let edit = TextEdit {
+            range: self.line_range(lines.clone()),
+            new_text: formatted[lines].join("\n"),
+        };
+        vec![CodeAction {
+            title: "Convert to the canonical form".to_owned(),
+            kind: Some(CodeActionKind::REFACTOR_REWRITE),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                ..WorkspaceEdit::default()
+            }),
+            ..CodeAction::default()
+        }]
+    }
+
+    /// The event on the line, or dated by the day header on the line.
+    fn event_at(&self, line: usize) -> Option<&Event> {
+        self.document.event_at(line).or_else(|| {
+            self.document
+                .events()
+                .find(|event| event.source.date.as_ref().map(|span| span.line) == Some(line))
+        })
+    }
+
+    /// The range of the span, in the UTF-16 code units of the protocol.
+    fn range(&self, span: &Span) -> Range {
+        let line_start = self.text[..span.bytes.start]
+            .rfind('\n')
+            .map_or(0, |i| i + 1);
+        let character = |byte: usize| self.text[line_start..byte].encode_utf16().count() as u32;
+        Range::new(
+            Position::new(span.line as u32, character(span.bytes.start)),
+            Position::new(span.line as u32, character(span.bytes.end)),
+        )
+    }
+
+    /// The range covering the whole lines.
+    fn line_range(&self, lines: std::ops::Range<usize>) -> Range {
+        let last = self
+            .text
+            .split('\n')
+            .nth(lines.end - 1)
+            .map_or(0, |line| line.encode_utf16().count());
+        Range::new(
+            Position::new(lines.start as u32, 0),
+            Position::new(lines.end as u32 - 1, last as u32),
+        )
+    }
+}
+
+/// The byte index of the position in UTF-16 code units on the line.
+fn byte_index(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= character as usize {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+fn is_tag_char(c: char) -> bool {
+    Tag::ALL.iter().any(|tag| tag.key().contains(c))
+}
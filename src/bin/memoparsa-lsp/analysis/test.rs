@@ -0,0 +1,116 @@
+use super::{format_of, File};
+use chrono::NaiveDate;
+use lsp_types::{HoverContents, Position, Range, Url};
+use memoparsa::{Registry, SourceFormat};
+
+const SOURCE: &str = "\
+    04.09       tilit
+ma  03.09   12:00-13:00   p   lounas (@Hervanta)
+\t25.9    pr  vuokra
+    31.02       karkauspäivä
+";
+
+fn alpha() -> File {
+    File::new(SourceFormat::Alpha, 2019, SOURCE.to_owned())
+}
+
+#[test]
+fn files_are_told_apart_by_name() {
+    let registry = Registry::default();
+    let uri = Url::parse("file:///home/notes/CALENDAR.md").unwrap();
+    assert_eq!(format_of(&registry, &uri), SourceFormat::Calendar);
+    let uri = Url::parse("file:///home/notes/alpha2.md").unwrap();
+    assert_eq!(format_of(&registry, &uri), SourceFormat::Alpha);
+    let uri = Url::parse("file:///home/notes/muistiinpanot.md").unwrap();
+    assert_eq!(format_of(&registry, &uri), SourceFormat::Alpha);
+}
+
+#[test]
+fn diagnostics_point_at_the_dates() {
+    let today = NaiveDate::from_ymd_opt(2019, 6, 1).unwrap();
+    let diagnostics = alpha().diagnostics(today);
+
+    let found = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.range, diagnostic.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        found,
+        vec![
+            (
+                Range::new(Position::new(1, 4), Position::new(1, 23)),
+                "2019-09-03 is before 2019-09-04 on line 1"
+            ),
+            (
+                Range::new(Position::new(1, 4), Position::new(1, 23)),
+                "2019-09-03 is a Tue, not Mon"
+            ),
+            (
+                Range::new(Position::new(3, 4), Position::new(3, 9)),
+                "\"31.02\" is not a valid date"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn hover_shows_the_resolved_date() {
+    let hover = alpha().hover(Position::new(1, 30)).unwrap();
+    let value = match hover.contents {
+        HoverContents::Markup(markup) => markup.value,
+        contents => panic!("unexpected contents {:?}", contents),
+    };
+    assert_eq!(value, "**Tue 2019-09-03 12:00-13:00**\n\ntags: publish");
+    assert!(alpha().hover(Position::new(4, 0)).is_none());
+
+    // the day header of a calendar dates its events
+    let calendar = File::new(
+        SourceFormat::Calendar,
+        2019,
+        "### Pe 30.8.\n* 12:00 lounas\n".to_owned(),
+    );
+    assert!(calendar.hover(Position::new(0, 5)).is_some());
+    assert!(calendar.hover(Position::new(1, 3)).is_some());
+}
+
+#[test]
+fn tags_are_completed_in_the_tag_column() {
+    let labels = |file: &File, position| {
+        file.completion(position)
+            .into_iter()
+            .map(|item| item.label)
+            .collect::<Vec<String>>()
+    };
+
+    let file = alpha();
+    let tags = labels(&file, Position::new(2, 10));
+    assert!(tags.contains(&"H".to_owned()));
+    assert!(!tags.contains(&"p".to_owned()));
+    // not in the description nor in the date
+    assert!(labels(&file, Position::new(2, 16)).is_empty());
+    assert!(labels(&file, Position::new(0, 3)).is_empty());
+}
+
+#[test]
+fn entries_convert_to_the_canonical_form() {
+    let uri = Url::parse("file:///home/notes/alpha.md").unwrap();
+    let file = alpha();
+
+    let actions = file.code_actions(&uri, Range::new(Position::new(2, 0), Position::new(2, 0)));
+    assert_eq!(actions.len(), 1);
+    let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri];
+    assert_eq!(
+        edits[0].range,
+        Range::new(Position::new(2, 0), Position::new(2, 19))
+    );
+    assert_eq!(edits[0].new_text, "    25.09           pr  vuokra");
+
+    // the entry is already in the canonical form
+    let canonical = File::new(
+        SourceFormat::Alpha,
+        2019,
+        "    25.09           pr  vuokra\n".to_owned(),
+    );
+    let range = Range::new(Position::new(0, 0), Position::new(0, 0));
+    assert!(canonical.code_actions(&uri, range).is_empty());
+}
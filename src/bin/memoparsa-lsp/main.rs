@@ -0,0 +1,208 @@
+//! A language server for alpha and calendar files, talking over stdio.
+#[macro_use]
+extern crate log;
+
+mod analysis;
+#[cfg(test)]
+mod test;
+
+use analysis::File;
+use chrono::prelude::*;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::{CodeActionRequest, Completion, HoverRequest, Request as RequestTrait};
+use lsp_types::{
+    CodeActionOrCommand, CodeActionProviderCapability, CompletionOptions, HoverProviderCapability,
+    InitializeParams, PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+use memoparsa::Registry;
+use std::collections::HashMap;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    // log to stderr, stdout is the connection to the editor
+    env_logger::init();
+
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        ..ServerCapabilities::default()
+    };
+    let params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+
+    // the start year of alpha files can be set with `{"startYear": 2019}`
+    let year = params
+        .initialization_options
+        .and_then(|options| options.get("startYear")?.as_i64())
+        .map_or_else(|| Local::now().year(), |year| year as i32);
+
+    Server {
+        connection: &connection,
+        year,
+        registry: Registry::default(),
+        files: HashMap::new(),
+    }
+    .run()?;
+    // the writer thread ends when the connection is dropped
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+struct Server<'a> {
+    connection: &'a Connection,
+    year: i32,
+    registry: Registry,
+    files: HashMap<Url, File>,
+}
+
+impl Server<'_> {
+    fn run(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for message in &self.connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.respond(request);
+                    self.connection.sender.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => self.notify(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers the request, or tells the editor its params were malformed.
+    fn respond(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        match self.result(request) {
+            Ok(result) => Response {
+                id,
+                result: Some(result.unwrap_or(serde_json::Value::Null)),
+                error: None,
+            },
+            Err(error) => {
+                warn!("malformed request: {}", error);
+                Response::new_err(id, ErrorCode::InvalidParams as i32, error.to_string())
+            }
+        }
+    }
+
+    fn result(&self, request: Request) -> Result<Option<serde_json::Value>, serde_json::Error> {
+        let result = match request.method.as_str() {
+            HoverRequest::METHOD => {
+                let (_, params) = extract::<HoverRequest>(request)?;
+                let position = params.text_document_position_params;
+                self.files
+                    .get(&position.text_document.uri)
+                    .and_then(|file| file.hover(position.position))
+                    .map(serde_json::to_value)
+                    .transpose()?
+            }
+            Completion::METHOD => {
+                let (_, params) = extract::<Completion>(request)?;
+                let position = params.text_document_position;
+                let items = self
+                    .files
+                    .get(&position.text_document.uri)
+                    .map(|file| file.completion(position.position))
+                    .unwrap_or_default();
+                Some(serde_json::to_value(items)?)
+            }
+            CodeActionRequest::METHOD => {
+                let (_, params) = extract::<CodeActionRequest>(request)?;
+                let (uri, range) = (params.text_document.uri, params.range);
+                let actions = self
+                    .files
+                    .get(&uri)
+                    .map(|file| file.code_actions(&uri, range))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(CodeActionOrCommand::CodeAction)
+                    .collect::<Vec<_>>();
+                Some(serde_json::to_value(actions)?)
+            }
+            method => {
+                debug!("unhandled request {}", method);
+                None
+            }
+        };
+        Ok(result)
+    }
+
+    /// Handles the notification. A malformed notification has no one to tell, so it is only
+    /// logged.
+    fn notify(&mut self, notification: Notification) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                if let Some(params) = params::<DidOpenTextDocument>(notification) {
+                    let document = params.text_document;
+                    self.update(document.uri, document.text)?;
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                if let Some(params) = params::<DidChangeTextDocument>(notification) {
+                    // the changes are whole documents, as the sync kind is full
+                    if let Some(change) = params.content_changes.into_iter().last() {
+                        self.update(params.text_document.uri, change.text)?;
+                    }
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                if let Some(params) = params::<DidCloseTextDocument>(notification) {
+                    self.files.remove(&params.text_document.uri);
+                }
+            }
+            method => debug!("unhandled notification {}", method),
+        }
+        Ok(())
+    }
+
+    /// Parses the new text of the file and publishes its diagnostics.
+    fn update(&mut self, uri: Url, text: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let file = File::new(analysis::format_of(&self.registry, &uri), self.year, text);
+        let params = PublishDiagnosticsParams {
+            uri: uri.clone(),
+            diagnostics: file.diagnostics(Local::now().date_naive()),
+            version: None,
+        };
+        self.files.insert(uri, file);
+        self.connection
+            .sender
+            .send(Message::Notification(Notification::new(
+                PublishDiagnostics::METHOD.to_owned(),
+                params,
+            )))?;
+        Ok(())
+    }
+}
+
+fn params<N>(notification: Notification) -> Option<N::Params>
+where
+    N: NotificationTrait,
+{
+    notification
+        .extract(N::METHOD)
+        .map_err(|error| warn!("malformed notification: {}", error))
+        .ok()
+}
+
+fn extract<R>(request: Request) -> Result<(lsp_server::RequestId, R::Params), serde_json::Error>
+where
+    R: RequestTrait,
+{
+    request.extract(R::METHOD).map_err(|error| match error {
+        lsp_server::ExtractError::JsonError { error, .. } => error,
+        lsp_server::ExtractError::MethodMismatch(_) => unreachable!(),
+    })
+}
@@ -0,0 +1,49 @@
+use super::Server;
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use memoparsa::Registry;
+use std::collections::HashMap;
+
+#[test]
+fn malformed_notifications_do_not_stop_the_server() {
+    let (server, client) = Connection::memory();
+    let send = |message: Message| client.sender.send(message).unwrap();
+
+    send(Message::Notification(Notification::new(
+        "textDocument/didOpen".to_owned(),
+        serde_json::json!({ "textDocument": "alpha.md" }),
+    )));
+    send(Message::Request(Request::new(
+        RequestId::from(1),
+        "textDocument/completion".to_owned(),
+        serde_json::json!({
+            "textDocument": { "uri": "file:///home/notes/alpha.md" },
+            "position": { "line": 0, "character": 0 },
+        }),
+    )));
+    send(Message::Request(Request::new(
+        RequestId::from(2),
+        "shutdown".to_owned(),
+        serde_json::Value::Null,
+    )));
+    send(Message::Notification(Notification::new(
+        "exit".to_owned(),
+        serde_json::Value::Null,
+    )));
+
+    Server {
+        connection: &server,
+        year: 2019,
+        registry: Registry::default(),
+        files: HashMap::new(),
+    }
+    .run()
+    .unwrap();
+
+    match client.receiver.recv().unwrap() {
+        Message::Response(response) => {
+            assert_eq!(response.id, RequestId::from(1));
+            assert_eq!(response.result, Some(serde_json::json!([])));
+        }
+        message => panic!("expected a response, got {:?}", message),
+    }
+}
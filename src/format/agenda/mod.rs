@@ -0,0 +1,114 @@
+#[cfg(test)]
+mod test;
+
+use super::*;
+
+/// An event on a day of an agenda.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Occurrence<'a> {
+    pub day: NaiveDate,
+    pub event: &'a Event,
+    /// The day of an event spanning several days and the number of its days, eg. `(2, 3)` for
+    /// the second day of three.
+    pub span_day: Option<(i64, i64)>,
+}
+
+impl Occurrence<'_> {
+    /// The time the event starts on the day, none for the whole day or a day within a span.
+    pub fn start_time(&self) -> Option<NaiveTime> {
+        if self.span_day.is_some_and(|(day, _)| day > 1) {
+            return None;
+        }
+        match &self.event.date {
+            DateVariant::TimeSpan(start, _) | DateVariant::DateTime(start) => Some(start.time()),
+            _ => None,
+        }
+    }
+}
+
+/// The events on the `days` days from `from` on, by day and by time, the events without a time
+/// first. Repeating events, and the events in sections named for repeating, eg.
+/// `### Annually repeating`, are shown on every day they repeat on, and the events spanning several
+/// days on each of the days. A repeating event starts on its date, except for the days of every
+/// month, eg. `04.xx`, and the events in the sections, whose year is only that of the file.
+pub fn agenda(events: &[Event], from: NaiveDate, days: usize) -> Vec<Occurrence<'_>> {
+    let mut occurrences = Vec::new();
+    for day in from.iter_days().take(days) {
+        let mut on_day = events
+            .iter()
+            .filter_map(|event| occurrence(event, day))
+            .collect::<Vec<Occurrence>>();
+        on_day.sort_by_key(Occurrence::start_time);
+        occurrences.extend(on_day);
+    }
+    occurrences
+}
+
+fn occurrence(event: &Event, day: NaiveDate) -> Option<Occurrence<'_>> {
+    let start = event.date.start_date();
+    if let Some(recurrence) = recurrence(event) {
+        let months = event
+            .section
+            .as_deref()
+            .map(section_months)
+            .unwrap_or_default();
+        // the day of every month and the sections only give the date a year to be in
+        let since_start = event.recurrence.is_some() && recurrence != Recurrence::Monthly;
+        let repeats = (day >= start || !since_start)
+            && match recurrence {
+                Recurrence::Monthly => {
+                    day.day() == start.day() && (months.is_empty() || months.contains(&day.month()))
+                }
+                Recurrence::Yearly => day.month() == start.month() && day.day() == start.day(),
+                // no format repeats daily or weekly
+                Recurrence::Daily | Recurrence::Weekly => false,
+            };
+        return repeats.then_some(Occurrence {
+            day,
+            event,
+            span_day: None,
+        });
+    }
+
+    // dates without a day are not on any day of the agenda
+    if let DateVariant::Month { .. } | DateVariant::Year(_) = event.date {
+        return None;
+    }
+    let end = event.date.end_date();
+    if day < start || day > end {
+        return None;
+    }
+    let span_day =
+        (start != end).then(|| ((day - start).num_days() + 1, (end - start).num_days() + 1));
+    Some(Occurrence {
+        day,
+        event,
+        span_day,
+    })
+}
+
+/// How the event repeats: by its own recurrence, or yearly in a section named for it, eg.
+/// `### Annually repeating`.
+fn recurrence(event: &Event) -> Option<Recurrence> {
+    event.recurrence.or_else(|| {
+        let section = event.section.as_deref()?.to_lowercase();
+        ["annually", "yearly", "vuosittain"]
+            .iter()
+            .any(|word| section.contains(word))
+            .then_some(Recurrence::Yearly)
+    })
+}
+
+/// The months named in a section, eg. 2, 6 and 10 for `Repeating 2/6/10 (4 kk)`, or none for
+/// every month.
+fn section_months(section: &str) -> Vec<u32> {
+    section
+        .split_whitespace()
+        .filter(|word| word.contains('/'))
+        .find_map(|word| {
+            word.split('/')
+                .map(|month| month.parse::<u32>().ok().filter(|m| (1..=12).contains(m)))
+                .collect::<Option<Vec<u32>>>()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,92 @@
+use super::{agenda, Occurrence};
+use crate::format::document::parse_alpha;
+use chrono::{Datelike, NaiveDate, NaiveTime};
+
+const SOURCE: &str = "\
+    02.05   12:00-13:00   p   lounas
+    02.05           p   vappu
+    03.05-05.05     konferenssi
+
+### Repeating
+    04.xx           tilit
+
+### Repeating 2/6/10 (4 kk)
+    05.xx           lataa bussikortti
+
+### Annually repeating
+    04.05           syntymäpäivä
+";
+
+fn day(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2019, month, day).unwrap()
+}
+
+#[test]
+fn days_show_their_events() {
+    let events = parse_alpha(2019, SOURCE).into_events();
+    let found = |from, days| {
+        agenda(&events, from, days)
+            .into_iter()
+            .map(|occurrence| {
+                (
+                    occurrence.day,
+                    occurrence.event.description.as_str(),
+                    occurrence.span_day,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        found(day(5, 2), 4),
+        vec![
+            // the events without a time come first
+            (day(5, 2), "vappu", None),
+            (day(5, 2), "lounas", None),
+            (day(5, 3), "konferenssi", Some((1, 3))),
+            (day(5, 4), "konferenssi", Some((2, 3))),
+            (day(5, 4), "tilit", None),
+            (day(5, 4), "syntymäpäivä", None),
+            (day(5, 5), "konferenssi", Some((3, 3))),
+        ]
+    );
+    // only in the months of the section
+    assert_eq!(
+        found(day(6, 5), 1),
+        vec![(day(6, 5), "lataa bussikortti", None)]
+    );
+    assert!(found(day(7, 5), 1).is_empty());
+    assert_eq!(found(day(5, 4).with_year(2020).unwrap(), 1).len(), 2);
+}
+
+#[test]
+fn spans_start_on_their_first_day() {
+    let events = parse_alpha(2019, "    03.05-05.05   10-   konferenssi\n").into_events();
+    let occurrences = agenda(&events, day(5, 3), 2);
+    assert_eq!(
+        occurrences
+            .iter()
+            .map(Occurrence::start_time)
+            .collect::<Vec<_>>(),
+        vec![NaiveTime::from_hms_opt(10, 0, 0), None]
+    );
+}
+
+#[test]
+fn repeating_events_are_shown_before_the_start_year() {
+    let events = parse_alpha(2019, include_str!("../../../data/alpha.md")).into_events();
+    let found = |from: NaiveDate| {
+        agenda(&events, from, 1)
+            .into_iter()
+            .map(|occurrence| occurrence.event.description.clone())
+            .collect::<Vec<_>>()
+    };
+
+    assert!(found(day(6, 4))
+        .iter()
+        .any(|description| description.starts_with("tilit")));
+    // in the annually repeating section after the year line 2021
+    assert!(found(day(1, 20).with_year(2020).unwrap())
+        .iter()
+        .any(|description| description == "Saskia's birthday"));
+}
@@ -1,5 +1,6 @@
 #[cfg(test)]
 mod test;
+pub mod writer;
 
 use super::*;
 use chrono::prelude::*;
@@ -7,102 +8,373 @@ use chrono::Duration;
 use std::collections::HashMap;
 
 lazy_static! {
-    static ref IGNORED_WEEKDAY_LABELS: Vec<&'static str> =
-        vec!["ma", "ti", "ke", "to", "pe", "la", "su", "Mo", "Tu", "We", "Th", "Fr", "Sa", "Su",];
+    // in lower case, the Finnish and the English labels do not overlap except for Sunday
+    static ref WEEKDAY_BY_LABEL: HashMap<&'static str, Weekday> = hashmap! {
+        "ma" => Weekday::Mon, "ti" => Weekday::Tue, "ke" => Weekday::Wed, "to" => Weekday::Thu,
+        "pe" => Weekday::Fri, "la" => Weekday::Sat, "su" => Weekday::Sun,
+        "mo" => Weekday::Mon, "tu" => Weekday::Tue, "we" => Weekday::Wed, "th" => Weekday::Thu,
+        "fr" => Weekday::Fri, "sa" => Weekday::Sat,
+    };
     static ref TAG_BY_KEYCHAR: HashMap<char, Tag> = hashmap! {
         'p' => Tag::PublishToIcs,
         '#' => Tag::Acknowledge,
+        'L' => Tag::LoadAtDate,
+        '+' => Tag::Derived,
+        'H' => Tag::Deadline,
+        '~' => Tag::Estimate,
+        '?' => Tag::Questionable,
+        'o' => Tag::Optional,
+        'T' => Tag::Exam,
+        '<' => Tag::OrEarlier,
+        '>' => Tag::OrLater,
+        'X' => Tag::CannotAttend,
+        'r' => Tag::HandleInAdvance,
     };
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+/// Tags from the legend of the alpha file. Each is marked with a single character in the tag
+/// column, except for `->`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Tag {
+    /// p
     PublishToIcs,
+    /// #
     Acknowledge,
+    /// L, load-at-date
+    LoadAtDate,
+    /// +, derived from another entry
+    Derived,
+    /// H, deadline
+    Deadline,
+    /// ~, estimate
+    Estimate,
+    /// ?, questionable
+    Questionable,
+    /// o, optional
+    Optional,
+    /// T, exam
+    Exam,
+    /// <, or earlier
+    OrEarlier,
+    /// >, or later
+    OrLater,
+    /// ->, away from home
+    AwayFromHome,
+    /// X, can't make it
+    CannotAttend,
+    /// r, must be handled in advance
+    HandleInAdvance,
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub struct Event {
-    pub date: DateVariant,
-    pub tags: Vec<Tag>,
-    pub description: String,
+impl Tag {
+    /// The tags in the order of the legend.
+    pub const ALL: [Tag; 14] = [
+        Tag::PublishToIcs,
+        Tag::Acknowledge,
+        Tag::LoadAtDate,
+        Tag::Derived,
+        Tag::Deadline,
+        Tag::Estimate,
+        Tag::Questionable,
+        Tag::Optional,
+        Tag::Exam,
+        Tag::OrEarlier,
+        Tag::OrLater,
+        Tag::AwayFromHome,
+        Tag::CannotAttend,
+        Tag::HandleInAdvance,
+    ];
+
+    /// A name for the tag, as used in calendar categories.
+    pub fn name(self) -> &'static str {
+        match self {
+            Tag::PublishToIcs => "publish",
+            Tag::Acknowledge => "acknowledge",
+            Tag::LoadAtDate => "load-at-date",
+            Tag::Derived => "derived",
+            Tag::Deadline => "deadline",
+            Tag::Estimate => "estimate",
+            Tag::Questionable => "questionable",
+            Tag::Optional => "optional",
+            Tag::Exam => "exam",
+            Tag::OrEarlier => "or-earlier",
+            Tag::OrLater => "or-later",
+            Tag::AwayFromHome => "away-from-home",
+            Tag::CannotAttend => "cannot-attend",
+            Tag::HandleInAdvance => "handle-in-advance",
+        }
+    }
+
+    /// The key of the tag in the legend, as written in the tag column.
+    pub fn key(self) -> &'static str {
+        match self {
+            Tag::PublishToIcs => "p",
+            Tag::Acknowledge => "#",
+            Tag::LoadAtDate => "L",
+            Tag::Derived => "+",
+            Tag::Deadline => "H",
+            Tag::Estimate => "~",
+            Tag::Questionable => "?",
+            Tag::Optional => "o",
+            Tag::Exam => "T",
+            Tag::OrEarlier => "<",
+            Tag::OrLater => ">",
+            Tag::AwayFromHome => "->",
+            Tag::CannotAttend => "X",
+            Tag::HandleInAdvance => "r",
+        }
+    }
+}
+
+impl std::str::FromStr for Tag {
+    type Err = ParseError;
+
+    /// Parses a tag from its key in the legend, eg. `p` or `->`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (s, chars.next(), chars.next()) {
+            ("->", _, _) => Ok(Tag::AwayFromHome),
+            (_, Some(c), None) => TAG_BY_KEYCHAR
+                .get(&c)
+                .copied()
+                .ok_or_else(|| ParseError(format!("unknown tag: {}", s))),
+            _ => Err(ParseError(format!("unknown tag: {}", s))),
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct ParseError(String);
+pub struct ParseError(pub(crate) String);
 
-fn parse_datespan(s: &str, year_of_start: i32) -> Option<(NaiveDate, NaiveDate)> {
-    let datespan_parts = s.split('-').collect::<Vec<&str>>();
-    if datespan_parts.len() != 2 {
-        return None;
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
     }
-    let left = datespan_parts[0];
-    let right = datespan_parts[1];
+}
+
+impl std::error::Error for ParseError {}
+
+fn parse_datespan(s: &str, year_of_start: i32) -> Option<(NaiveDate, NaiveDate)> {
+    // the dash of a span is followed by the end date, a dash followed by a bare year is a year
+    // suffix, eg. `1.7.-19`
+    let (left, right) = s
+        .match_indices('-')
+        .map(|(idx, _)| (&s[..idx], &s[idx + 1..]))
+        .find(|(left, right)| !left.is_empty() && right.contains('.'))?;
 
     // end date must exist in full-form in a time span
-    let end_date = match parse_date(right, year_of_start) {
-        Some(date) => date,
-        None => return None,
-    };
+    let (end_day, end_month, end_year) = parse_date_parts(right)?;
 
-    let start_date = {
-        // try parsing the thing preceding the dash as the start date
-        if let Some(date) = parse_date(left, year_of_start) {
-            date
-        } else {
+    // try parsing the thing preceding the dash as the start date
+    let (start_day, start_month, start_year) = match parse_date_parts(left) {
+        Some(parts) => parts,
+        None => {
             // parse thing preceding dash as the day
-            let day = match left.trim_end_matches('.').parse::<u32>() {
-                Ok(i) => i,
-                Err(_) => return None,
-            };
+            let day = left.trim_end_matches('.').parse::<u32>().ok()?;
 
             // take month from end-date as the start date didn't specify it
-            let month = end_date.month();
-            NaiveDate::from_ymd(year_of_start, month, day)
+            (day, end_month, None)
         }
     };
 
-    // if end date is before start date, the end date was probably of the next year
-    if start_date < end_date {
-        Some((start_date, end_date))
-    } else {
-        Some((
-            start_date,
-            // HACK: Duration::days gets broken by leap days (year is actually 365.25)
-            end_date.checked_add_signed(Duration::days(365)).unwrap(),
-        ))
-    }
+    let (start_date, end_date) = match (start_year, end_year) {
+        (Some(start_year), Some(end_year)) => (
+            NaiveDate::from_ymd_opt(start_year, start_month, start_day)?,
+            NaiveDate::from_ymd_opt(end_year, end_month, end_day)?,
+        ),
+        // if the end date is before the start date, it is of the next year
+        (start_year, None) => {
+            let start_year = start_year.unwrap_or(year_of_start);
+            let start_date = NaiveDate::from_ymd_opt(start_year, start_month, start_day)?;
+            let mut end_date = NaiveDate::from_ymd_opt(start_year, end_month, end_day)?;
+            if end_date < start_date {
+                end_date = NaiveDate::from_ymd_opt(start_year + 1, end_month, end_day)?;
+            }
+            (start_date, end_date)
+        }
+        // if the start date is after the end date, it is of the previous year
+        (None, Some(end_year)) => {
+            let end_date = NaiveDate::from_ymd_opt(end_year, end_month, end_day)?;
+            let mut start_date = NaiveDate::from_ymd_opt(end_year, start_month, start_day)?;
+            if start_date > end_date {
+                start_date = NaiveDate::from_ymd_opt(end_year - 1, start_month, start_day)?;
+            }
+            (start_date, end_date)
+        }
+    };
+
+    Some((start_date, end_date))
 }
 
-fn maybe_parse_and_consume_tags(parts: &mut Vec<&str>) -> Vec<Tag> {
+/// The tags with the text of their keys, and the reminders.
+type TagsAndReminders<'a> = (Vec<(Tag, &'a str)>, Vec<Duration>);
+
+/// Parses the tag column: tag characters optionally followed by reminders, eg. `pr!2d`, and
+/// separate reminder tokens, eg. `p !2d !1h`.
+fn maybe_parse_and_consume_tags<'a>(parts: &mut Vec<&'a str>) -> TagsAndReminders<'a> {
     // pick out the first continuous stream of tokens as the tag list candidate
-    let candidate = parts[0];
+    let candidate = match parts.first() {
+        Some(candidate) => candidate,
+        None => return (vec![], vec![]),
+    };
+    let (tags, mut reminders) = match parse_tag_token(candidate) {
+        Some(tags_and_reminders) => tags_and_reminders,
+        None => return (vec![], vec![]),
+    };
+    parts.remove(0);
 
-    let mut tags: Vec<Tag> = vec![];
-    for c in candidate.chars() {
-        match TAG_BY_KEYCHAR.get(&c) {
-            Some(tag) => tags.push(*tag),
-            None => return vec![],
-        };
+    // further tokens can only add reminders
+    while let Some(candidate) = parts.first() {
+        match parse_tag_token(candidate) {
+            Some((tags, more_reminders)) if tags.is_empty() && !more_reminders.is_empty() => {
+                reminders.extend(more_reminders);
+                parts.remove(0);
+            }
+            _ => break,
+        }
     }
 
-    // success: remove the tag candidate and return tags
-    parts.remove(0);
-    tags
+    (tags, reminders)
 }
 
-fn maybe_remove_weekday_label(parts: &mut Vec<&str>) {
-    // remove the first part if it's the weekday label
-    if IGNORED_WEEKDAY_LABELS.contains(parts.first().unwrap()) {
-        parts.remove(0);
+fn parse_tag_token(token: &str) -> Option<TagsAndReminders<'_>> {
+    let mut segments = token.split('!');
+    let tag_chars = segments.next().unwrap_or("");
+
+    let mut tags: Vec<(Tag, &str)> = vec![];
+    let mut chars = tag_chars.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '-' && chars.peek().map(|(_, c)| *c) == Some('>') {
+            chars.next();
+            tags.push((Tag::AwayFromHome, &tag_chars[i..i + 2]));
+            continue;
+        }
+        tags.push((*TAG_BY_KEYCHAR.get(&c)?, &tag_chars[i..i + c.len_utf8()]));
+    }
+
+    let reminders = segments
+        .map(parse_offset)
+        .collect::<Option<Vec<Duration>>>()?;
+    if tags.is_empty() && reminders.is_empty() {
+        return None;
+    }
+    Some((tags, reminders))
+}
+
+/// Parses a date with an unknown day or month, eg. `xx.07`, `--.01` or `xx.xx`.
+fn parse_imprecise_date(s: &str, year: i32) -> Option<DateVariant> {
+    let parts = s.trim_end_matches('.').split('.').collect::<Vec<&str>>();
+    let unknown = |part: &str| part == "xx" || part == "--";
+    match parts.as_slice() {
+        [day, month] if unknown(day) && unknown(month) => Some(DateVariant::Year(year as u32)),
+        [day, month] if unknown(day) => {
+            let month = month.parse::<u32>().ok()?;
+            if !(1..=12).contains(&month) {
+                return None;
+            }
+            Some(DateVariant::Month {
+                year: year as u32,
+                month,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Parses the time that follows a weekday in place of the date, eg. `18:00` in `pe 18:00`.
+fn parse_weekday_time(
+    s: &str,
+    weekday: Weekday,
+    after: NaiveDate,
+    timezone: Tz,
+) -> Option<DateVariant> {
+    let times = parse_time_ranges(s)?;
+    let date = next_weekday(after, weekday);
+    resolve_times(date, date, &times, timezone)
+}
 
-        trace!("removed weekday-label");
-        trace!("> {:?}", &parts);
+/// Parses a day of every month, eg. `04.xx`, as the day in the first month of the year.
+fn parse_monthly_date(s: &str, year: i32) -> Option<DateVariant> {
+    let parts = s.trim_end_matches('.').split('.').collect::<Vec<&str>>();
+    match parts.as_slice() {
+        [day, "xx"] | [day, "--"] => {
+            let day = day.parse::<u32>().ok()?;
+            NaiveDate::from_ymd_opt(year, 1, day).map(DateVariant::Date)
+        }
+        _ => None,
     }
 }
 
+/// Finds the place marked with `@` in a parenthesized remark, eg. `(@Hervanta)`.
+fn parse_location(description: &str) -> Option<String> {
+    let start = description.find("(@")? + 2;
+    let end = start + description[start..].find(')')?;
+    let location = description[start..end].trim();
+    if location.is_empty() {
+        return None;
+    }
+    Some(location.to_owned())
+}
+
+/// Strips the `~` that marks an approximate date or time, eg. `~14.4.` or `~12:00`.
+fn strip_approximate(s: &str) -> (&str, bool) {
+    match s.strip_prefix('~') {
+        Some(rest) => (rest, true),
+        None => (s, false),
+    }
+}
+
+/// Parses a Finnish or an English weekday label in any case, eg. `ma`, `Pe` or `Mo`.
+pub fn parse_weekday(label: &str) -> Option<Weekday> {
+    WEEKDAY_BY_LABEL.get(label.to_lowercase().as_str()).copied()
+}
+
+/// The Finnish label of the weekday, eg. `ma`.
+pub fn weekday_label(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "ma",
+        Weekday::Tue => "ti",
+        Weekday::Wed => "ke",
+        Weekday::Thu => "to",
+        Weekday::Fri => "pe",
+        Weekday::Sat => "la",
+        Weekday::Sun => "su",
+    }
+}
+
+/// The first day after `after` that is the weekday, eg. the next Friday after a Friday is a week
+/// later.
+pub fn next_weekday(after: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let days = (6 + weekday.num_days_from_monday() - after.weekday().num_days_from_monday()) % 7;
+    after + Duration::days(days as i64 + 1)
+}
+
+fn maybe_remove_weekday_label(parts: &mut Vec<&str>) -> Option<Weekday> {
+    // remove the first part if it's the weekday label
+    let weekday = parse_weekday(parts.first()?)?;
+    parts.remove(0);
+
+    trace!("removed weekday-label");
+    trace!("> {:?}", &parts);
+    Some(weekday)
+}
+
 impl Event {
+    /// Parses an event from a line of an alpha file, with `year` as the year of dates without one.
+    /// The times are in Europe/Helsinki.
     pub fn from_str(s: &str, year: i32) -> Result<Self, ParseError> {
+        Event::from_str_after(s, year, None, TZ)
+    }
+
+    /// Parses an event like `from_str`, with the times in `timezone`, also accepting a weekday and
+    /// a time in place of the date, eg. `pe 18:00`, for the first such weekday after `after`.
+    pub fn from_str_after(
+        s: &str,
+        year: i32,
+        after: Option<NaiveDate>,
+        timezone: Tz,
+    ) -> Result<Self, ParseError> {
         debug!("start parsing Event::from_str(\"{}\", {})", s, year);
 
         // split input string into parts on whitespace
@@ -111,113 +383,124 @@ impl Event {
             return Err(ParseError(format!("could not parse Event from \"{}\", {} is not enough elements to create both date and description", s, parts.len())));
         }
 
+        let mut approximate = false;
+        let mut recurrence = None;
+        // if the first element is identified as a weekday label, remove it
+        let weekday = maybe_remove_weekday_label(&mut parts);
+        let date_tokens = parts.clone();
         let date = {
-            // if the first element is identified as a weekday label, remove it
-            maybe_remove_weekday_label(&mut parts);
-
-            // try parse time from the second element
-            let time_result = parse_time(parts[1]);
-
-            let mut datevariant = None;
-            // try parse a date-span from the first element
-            if let Some((start_date, end_date)) = parse_datespan(parts.first().unwrap(), year) {
-                trace!("parsed date-span: {:?}", (start_date, end_date));
-
-                // HACK: 23:59 for end time seems like a sensible default
-                let end_date_time = end_date.and_hms(23, 59, 0);
-                // try add a start time from the second element
-                let start_date_time = match time_result {
-                    Some(time) => start_date.and_hms(time.hour(), time.minute(), 0),
-                    // HACK: 06:00 seems like a sensible default
-                    None => start_date.and_hms(6, 0, 0),
+            let (date_token, approximate_date) = strip_approximate(parts[0]);
+            approximate |= approximate_date;
+
+            // a date with an unknown day, eg. `xx.07`, cannot have times
+            if let Some(date) = parse_imprecise_date(date_token, year) {
+                trace!("parsed imprecise date: {:?}", date);
+                parts.remove(0);
+                trace!("consumed date");
+                trace!("> {:?}", &parts);
+                date
+            } else if let Some(date) = weekday.zip(after).and_then(|(weekday, after)| {
+                parse_weekday_time(date_token, weekday, after, timezone)
+            }) {
+                trace!("parsed date from weekday: {:?}", date);
+                parts.remove(0);
+                trace!("consumed time");
+                trace!("> {:?}", &parts);
+                date
+            } else if let Some(date) = parse_monthly_date(date_token, year) {
+                trace!("parsed monthly date: {:?}", date);
+                recurrence = Some(Recurrence::Monthly);
+                parts.remove(0);
+                trace!("consumed date");
+                trace!("> {:?}", &parts);
+                date
+            } else {
+                // try parse times from the second element, a lone `~` marks an approximate time
+                let (time_token, approximate_time) = strip_approximate(parts.get(1).unwrap_or(&""));
+                let times = match time_token {
+                    "" if approximate_time => Some(vec![]),
+                    time_token => parse_time_ranges(time_token),
                 };
+                approximate |= approximate_time && times.is_some();
 
-                datevariant = Some(DateVariant::TimeSpan(
-                    TZ.from_local_datetime(&start_date_time)
-                        .unwrap()
-                        .with_timezone(&Local),
-                    TZ.from_local_datetime(&end_date_time)
-                        .unwrap()
-                        .with_timezone(&Local),
-                ));
-            }
-            // try parse a date from the first element
-            else if let Some(date) = parse_date(parts.first().unwrap(), year) {
-                trace!("parsed date: {:?}", date);
-
-                // try add a start time from the second element
-                let dv;
-                if let Some(time) = time_result {
-                    let date_time = date.and_hms(time.hour(), time.minute(), 0);
-                    dv = DateVariant::DateTime(
-                        TZ.from_local_datetime(&date_time)
-                            .unwrap()
-                            .with_timezone(&Local),
-                    );
-                } else {
-                    // if it's a one day event, try for a time-span on the element[1]
-                    if let Some((start_time, end_time)) = parse_timespan(parts[1]) {
-                        // if a time-span was parsed, consume that
-                        parts.remove(1);
-
-                        let start_date = date.and_hms(start_time.hour(), start_time.minute(), 0);
-                        let end_date = date.and_hms(end_time.hour(), end_time.minute(), 0);
-                        dv = DateVariant::TimeSpan(
-                            TZ.from_local_datetime(&start_date)
-                                .unwrap()
-                                .with_timezone(&Local),
-                            TZ.from_local_datetime(&end_date)
-                                .unwrap()
-                                .with_timezone(&Local),
-                        );
-                    } else {
-                        dv = DateVariant::Date(
-                            TZ.from_local_date(&date).unwrap().with_timezone(&Local),
-                        );
-                    }
-                }
-                datevariant = Some(dv);
-            };
+                // try parse a date-span or a date from the first element
+                let dates = parse_datespan(date_token, year)
+                    .or_else(|| parse_date(date_token, year).map(|date| (date, date)));
+                let (start_date, end_date) = match dates {
+                    Some(dates) => dates,
+                    None => return Err(ParseError(format!("could not parse date from {}", s))),
+                };
+                trace!("parsed dates: {:?}", (start_date, end_date));
 
-            // if a date was parsed, consume the strings used in making it
-            if datevariant.is_some() {
+                // consume the strings used in making the date
                 parts.remove(0);
                 trace!("consumed date");
                 trace!("> {:?}", &parts);
 
-                // if a time was parsed, consume the strings used in making it
-                if time_result.is_some() {
+                // if times were parsed, consume the strings used in making them
+                if let Some(times) = &times {
+                    trace!("parsed times: {:?}", times);
                     parts.remove(0);
                     trace!("consumed time");
                     trace!("> {:?}", &parts);
                 }
 
-                datevariant.unwrap()
-            } else {
-                return Err(ParseError(format!("could not parse date from {}", s)));
+                let times = times.as_deref().unwrap_or(&[]);
+                match resolve_times(start_date, end_date, times, timezone) {
+                    Some(date) => date,
+                    None => {
+                        return Err(ParseError(format!(
+                            "could not fit the times to the days in {}",
+                            s
+                        )))
+                    }
+                }
             }
         };
 
+        // the date and the times are the tokens consumed in parsing the date
+        let consumed = date_tokens.len() - parts.len();
+        let date_span = Span::of(s, date_tokens[0]).to(&Span::of(s, date_tokens[consumed - 1]));
+
+        if let Some(weekday) = weekday {
+            let date = date.start_date();
+            if weekday != date.weekday() {
+                warn!(
+                    "{} is a {}, not {} as labeled in \"{}\"",
+                    date,
+                    date.weekday(),
+                    weekday,
+                    s
+                );
+            }
+        }
+
         // parse tags if possible
-        let tags = maybe_parse_and_consume_tags(&mut parts);
+        let (tags, reminders) = maybe_parse_and_consume_tags(&mut parts);
+        let (tags, tag_spans): (Vec<Tag>, Vec<Span>) = tags
+            .into_iter()
+            .map(|(tag, key)| (tag, Span::of(s, key)))
+            .unzip();
+        approximate |= tags.contains(&Tag::Estimate);
 
         let description = parts.join(" ");
+        let source = Source {
+            span: Span::from_bytes(s, s.len() - s.trim_start().len()..s.trim_end().len()),
+            date: Some(date_span),
+            tags: tag_spans,
+            ..Source::new(SourceFormat::Alpha.name())
+        };
 
         let event = Event {
-            date,
             tags,
-            description,
+            approximate,
+            reminders,
+            weekday,
+            location: parse_location(&description),
+            recurrence,
+            ..Event::new(date, &description, source)
         };
         debug!("parsed: {:?}", event);
         Ok(event)
     }
 }
-
-impl super::Event for Event {
-    fn date(&self) -> &DateVariant {
-        &self.date
-    }
-    fn description(&self) -> &str {
-        &self.description
-    }
-}
@@ -1,5 +1,6 @@
-use super::{DateVariant, Event, Tag};
+use super::{DateVariant, Event, Recurrence, Source, SourceFormat, Span, Tag};
 use chrono::prelude::*;
+use chrono::Duration;
 use chrono_tz::{Europe, Tz};
 use std::collections::HashMap;
 
@@ -10,50 +11,196 @@ struct Context {
 }
 const CTX: Context = Context { year: 2019 };
 
+/// An event parsed from a single line is on the first line of its source.
+fn alpha_line() -> Source {
+    Source::new(SourceFormat::Alpha.name())
+}
+
 lazy_static! {
     static ref EXAMPLE_EVENTS_BY_LINE: HashMap<&'static str, Event> = hashmap! {
         "    25.04          p    diplomityö Janille viimeistä kommentointia varten"
         => {
-            let local = TZ.ymd(CTX.year, 4, 25).with_timezone(&Local);
             let date =
-                DateVariant::Date(local);
+                DateVariant::Date(NaiveDate::from_ymd_opt(CTX.year, 4, 25).unwrap());
             Event {
-                date, tags: vec![Tag::PublishToIcs], description: "diplomityö Janille viimeistä kommentointia varten".to_owned()
+                tags: vec![Tag::PublishToIcs],
+                ..Event::new(date, "diplomityö Janille viimeistä kommentointia varten", alpha_line())
             }
         },
         "22.-27.10  # Rankka"
         => {
-            let local_start = TZ.ymd(CTX.year, 10, 22).and_hms(6, 0, 0).with_timezone(&Local);
-            let local_end = TZ.ymd(CTX.year, 10, 27).and_hms(23, 59, 0).with_timezone(&Local);
+            let local_start = TZ.with_ymd_and_hms(CTX.year, 10, 22, 6, 0, 0).unwrap().fixed_offset();
+            let local_end = TZ.with_ymd_and_hms(CTX.year, 10, 27, 23, 59, 0).unwrap().fixed_offset();
             let date = DateVariant::TimeSpan(local_start, local_end);
             Event {
-                date, tags: vec![Tag::Acknowledge], description: "Rankka".to_owned()
+                tags: vec![Tag::Acknowledge],
+                ..Event::new(date, "Rankka", alpha_line())
             }
         },
         "ma  25.04    23:59      [](PRML kirjan laina-aika päättyy)"
         => {
-            let local = TZ.ymd(CTX.year, 4, 25).and_hms(23, 59, 0).with_timezone(&Local);
+            let local = TZ.with_ymd_and_hms(CTX.year, 4, 25, 23, 59, 0).unwrap().fixed_offset();
             let date = DateVariant::DateTime(local);
             Event {
-                date, tags: vec![], description: "[](PRML kirjan laina-aika päättyy)".to_owned()
+                weekday: Some(Weekday::Mon),
+                ..Event::new(date, "[](PRML kirjan laina-aika päättyy)", alpha_line())
             }
         },
         "	25.-28.7			Saskia's Music Festival in late July"
         => {
-            let local_start = TZ.ymd(CTX.year, 7, 25).and_hms(6, 0, 0).with_timezone(&Local);
-            let local_end = TZ.ymd(CTX.year, 7, 28).and_hms(23, 59, 0).with_timezone(&Local);
+            let local_start = TZ.with_ymd_and_hms(CTX.year, 7, 25, 6, 0, 0).unwrap().fixed_offset();
+            let local_end = TZ.with_ymd_and_hms(CTX.year, 7, 28, 23, 59, 0).unwrap().fixed_offset();
+            let date = DateVariant::TimeSpan(local_start, local_end);
+            Event::new(date, "Saskia's Music Festival in late July", alpha_line())
+        },
+        "   03.09 12:40-13:20 p suuhygienisti Janni Sirola (@Hervanta)"
+        => {
+            let local_start = TZ.with_ymd_and_hms(CTX.year, 9, 3, 12, 40, 0).unwrap().fixed_offset();
+            let local_end = TZ.with_ymd_and_hms(CTX.year, 9, 3, 13, 20, 0).unwrap().fixed_offset();
             let date = DateVariant::TimeSpan(local_start, local_end);
             Event {
-                date, tags: vec![], description: "Saskia's Music Festival in late July".to_owned()
+                tags: vec![Tag::PublishToIcs],
+                location: Some("Hervanta".to_owned()),
+                ..Event::new(date, "suuhygienisti Janni Sirola (@Hervanta)", alpha_line())
             }
         },
-        "   03.09 12:40-13:20 p suuhygienisti Janni Sirola (@Hervanta)"
+        "    18.-19.5 11-,-19 p  staminaleiri 8 (avoin, ilmainen)"
+        => {
+            let local_start = TZ.with_ymd_and_hms(CTX.year, 5, 18, 11, 0, 0).unwrap().fixed_offset();
+            let local_end = TZ.with_ymd_and_hms(CTX.year, 5, 19, 19, 0, 0).unwrap().fixed_offset();
+            let date = DateVariant::TimeSpan(local_start, local_end);
+            Event {
+                tags: vec![Tag::PublishToIcs],
+                ..Event::new(date, "staminaleiri 8 (avoin, ilmainen)", alpha_line())
+            }
+        },
+        "ti  10.09   9-11        RTS-harkat (TC219)"
         => {
-            let local_start = TZ.ymd(CTX.year, 9, 3).and_hms(12, 40, 0).with_timezone(&Local);
-            let local_end = TZ.ymd(CTX.year, 9, 3).and_hms(13, 20, 0).with_timezone(&Local);
+            let local_start = TZ.with_ymd_and_hms(CTX.year, 9, 10, 9, 0, 0).unwrap().fixed_offset();
+            let local_end = TZ.with_ymd_and_hms(CTX.year, 9, 10, 11, 0, 0).unwrap().fixed_offset();
             let date = DateVariant::TimeSpan(local_start, local_end);
             Event {
-                date, tags: vec![Tag::PublishToIcs], description: "suuhygienisti Janni Sirola (@Hervanta)".to_owned()
+                weekday: Some(Weekday::Tue),
+                ..Event::new(date, "RTS-harkat (TC219)", alpha_line())
+            }
+        },
+        "    02.05   12:00-  p   palaveri"
+        => {
+            let local = TZ.with_ymd_and_hms(CTX.year, 5, 2, 12, 0, 0).unwrap().fixed_offset();
+            let date = DateVariant::DateTime(local);
+            Event {
+                tags: vec![Tag::PublishToIcs],
+                ..Event::new(date, "palaveri", alpha_line())
+            }
+        },
+        "    02.05   -19         sauna"
+        => {
+            let local_start = TZ.with_ymd_and_hms(CTX.year, 5, 2, 6, 0, 0).unwrap().fixed_offset();
+            let local_end = TZ.with_ymd_and_hms(CTX.year, 5, 2, 19, 0, 0).unwrap().fixed_offset();
+            let date = DateVariant::TimeSpan(local_start, local_end);
+            Event::new(date, "sauna", alpha_line())
+        },
+        "    25.05   ~        p  Stamina RPG alkaa"
+        => {
+            let date = DateVariant::Date(NaiveDate::from_ymd_opt(CTX.year, 5, 25).unwrap());
+            Event {
+                tags: vec![Tag::PublishToIcs],
+                approximate: true,
+                ..Event::new(date, "Stamina RPG alkaa", alpha_line())
+            }
+        },
+        "    ~14.04   ~12:00  ->  kokous"
+        => {
+            let local = TZ.with_ymd_and_hms(CTX.year, 4, 14, 12, 0, 0).unwrap().fixed_offset();
+            let date = DateVariant::DateTime(local);
+            Event {
+                tags: vec![Tag::AwayFromHome],
+                approximate: true,
+                ..Event::new(date, "kokous", alpha_line())
+            }
+        },
+        "    30.06           H~  selvitä verot"
+        => {
+            let date = DateVariant::Date(NaiveDate::from_ymd_opt(CTX.year, 6, 30).unwrap());
+            Event {
+                tags: vec![Tag::Deadline, Tag::Estimate],
+                approximate: true,
+                ..Event::new(date, "selvitä verot", alpha_line())
+            }
+        },
+        "    1.3.2020        H   hammaslääkäri"
+        => {
+            let date = DateVariant::Date(NaiveDate::from_ymd_opt(2020, 3, 1).unwrap());
+            Event {
+                tags: vec![Tag::Deadline],
+                ..Event::new(date, "hammaslääkäri", alpha_line())
+            }
+        },
+        "    1.7.-20             aikaisintaan"
+        => {
+            let date = DateVariant::Date(NaiveDate::from_ymd_opt(2020, 7, 1).unwrap());
+            Event::new(date, "aikaisintaan", alpha_line())
+        },
+        "    31.1.-2.2.      #   laskettelu"
+        => {
+            let date = DateVariant::TimeSpan(TZ.with_ymd_and_hms(CTX.year, 1, 31, 6, 0, 0).unwrap().fixed_offset(), TZ.with_ymd_and_hms(CTX.year, 2, 2, 23, 59, 0).unwrap().fixed_offset());
+            Event {
+                tags: vec![Tag::Acknowledge],
+                ..Event::new(date, "laskettelu", alpha_line())
+            }
+        },
+        "    28.12.-3.1.         uusivuosi"
+        => {
+            let date = DateVariant::TimeSpan(TZ.with_ymd_and_hms(CTX.year, 12, 28, 6, 0, 0).unwrap().fixed_offset(), TZ.with_ymd_and_hms(CTX.year + 1, 1, 3, 23, 59, 0).unwrap().fixed_offset());
+            Event::new(date, "uusivuosi", alpha_line())
+        },
+        "    28.12.-3.1.21       uusivuosi"
+        => {
+            let date = DateVariant::TimeSpan(TZ.with_ymd_and_hms(2020, 12, 28, 6, 0, 0).unwrap().fixed_offset(), TZ.with_ymd_and_hms(2021, 1, 3, 23, 59, 0).unwrap().fixed_offset());
+            Event::new(date, "uusivuosi", alpha_line())
+        },
+        "    28.2.2020-1.3.20    karkauspäivä"
+        => {
+            let date = DateVariant::TimeSpan(TZ.with_ymd_and_hms(2020, 2, 28, 6, 0, 0).unwrap().fixed_offset(), TZ.with_ymd_and_hms(2020, 3, 1, 23, 59, 0).unwrap().fixed_offset());
+            Event::new(date, "karkauspäivä", alpha_line())
+        },
+        "    20.12           pr!1w !1d  MP:n akku"
+        => {
+            let date = DateVariant::Date(NaiveDate::from_ymd_opt(CTX.year, 12, 20).unwrap());
+            Event {
+                tags: vec![Tag::PublishToIcs, Tag::HandleInAdvance],
+                reminders: vec![Duration::weeks(1), Duration::days(1)],
+                ..Event::new(date, "MP:n akku", alpha_line())
+            }
+        },
+        "    21.12   12:00   !30m    puhelu"
+        => {
+            let local = TZ.with_ymd_and_hms(CTX.year, 12, 21, 12, 0, 0).unwrap().fixed_offset();
+            let date = DateVariant::DateTime(local);
+            Event {
+                reminders: vec![Duration::minutes(30)],
+                ..Event::new(date, "puhelu", alpha_line())
+            }
+        },
+        "    xx.07            p   expected Rytmiitti in July"
+        => {
+            let date = DateVariant::Month { year: CTX.year as u32, month: 7 };
+            Event {
+                tags: vec![Tag::PublishToIcs],
+                ..Event::new(date, "expected Rytmiitti in July", alpha_line())
+            }
+        },
+        "	xx.xx				harkitse josko lisäis jotain likvidiä"
+        => {
+            let date = DateVariant::Year(CTX.year as u32);
+            Event::new(date, "harkitse josko lisäis jotain likvidiä", alpha_line())
+        },
+        "	04.xx				tilit (budjetti)"
+        => {
+            let date = DateVariant::Date(NaiveDate::from_ymd_opt(CTX.year, 1, 4).unwrap());
+            Event {
+                recurrence: Some(Recurrence::Monthly),
+                ..Event::new(date, "tilit (budjetti)", alpha_line())
             }
         },
     };
@@ -68,6 +215,92 @@ fn example_events_parse_correct() {
     init();
 
     for (key, value) in EXAMPLE_EVENTS_BY_LINE.iter() {
-        assert_eq!(Event::from_str(key, CTX.year).unwrap(), *value,);
+        let event = Event::from_str(key, CTX.year).unwrap();
+        // the spans are tested separately
+        let event = Event {
+            source: alpha_line(),
+            ..event
+        };
+        assert_eq!(event, *value);
     }
 }
+
+#[test]
+fn weekday_labels_are_parsed() {
+    init();
+
+    assert_eq!(super::parse_weekday("pe"), Some(Weekday::Fri));
+    assert_eq!(super::parse_weekday("Pe"), Some(Weekday::Fri));
+    assert_eq!(super::parse_weekday("Tu"), Some(Weekday::Tue));
+    assert_eq!(super::parse_weekday("pv"), None);
+
+    let after = NaiveDate::from_ymd_opt(CTX.year, 4, 23).unwrap();
+    let event = Event::from_str_after("pe 18:00 p sauna", CTX.year, Some(after), TZ).unwrap();
+    assert_eq!(event.weekday, Some(Weekday::Fri));
+    assert_eq!(
+        event.date.start_date(),
+        NaiveDate::from_ymd_opt(CTX.year, 4, 26).unwrap()
+    );
+    assert_eq!(event.description, "sauna");
+
+    // the next Friday after a Friday is a week later
+    let after = NaiveDate::from_ymd_opt(CTX.year, 4, 26).unwrap();
+    let event = Event::from_str_after("pe 18:00 p sauna", CTX.year, Some(after), TZ).unwrap();
+    assert_eq!(
+        event.date.start_date(),
+        NaiveDate::from_ymd_opt(CTX.year, 5, 3).unwrap()
+    );
+
+    // without a previous date the weekday alone is not a date
+    assert!(Event::from_str("pe 18:00 p sauna", CTX.year).is_err());
+}
+
+#[test]
+fn spans_point_at_the_tokens() {
+    init();
+
+    let line = "ma  25.04   23:59   pr!2d   laina-aika päättyy";
+    let event = Event::from_str(line, CTX.year).unwrap();
+    let text = |span: &Span| &line[span.bytes.clone()];
+
+    assert_eq!(text(&event.source.span), line);
+    assert_eq!(text(event.source.date.as_ref().unwrap()), "25.04   23:59");
+    let tags = event.source.tags.iter().map(text).collect::<Vec<&str>>();
+    assert_eq!(tags, vec!["p", "r"]);
+    assert_eq!(event.source.tags[1].columns, 21..22);
+}
+
+#[test]
+fn times_fit_the_days() {
+    init();
+
+    // a list has a time for each day, and spans from the start of the first to the end of the last
+    let span = |start: (u32, u32, u32), end: (u32, u32, u32)| {
+        DateVariant::TimeSpan(
+            TZ.with_ymd_and_hms(CTX.year, start.0, start.1, start.2, 0, 0)
+                .unwrap()
+                .fixed_offset(),
+            TZ.with_ymd_and_hms(CTX.year, end.0, end.1, end.2, 0, 0)
+                .unwrap()
+                .fixed_offset(),
+        )
+    };
+    let event = Event::from_str("    18.-19.5 10-16,10-14 konferenssi", CTX.year).unwrap();
+    assert_eq!(event.date, span((5, 18, 10), (5, 19, 14)));
+    let event = Event::from_str("    18.-20.5 9-17,9-17,9-12 kurssi", CTX.year).unwrap();
+    assert_eq!(event.date, span((5, 18, 9), (5, 20, 12)));
+    assert!(Event::from_str("    18.-19.5 11-,-19 p  staminaleiri", CTX.year).is_ok());
+
+    // the list must have a time for each day
+    assert!(Event::from_str("    20.05   9-11,13-15   kurssi", CTX.year).is_err());
+    assert!(Event::from_str("    18.-20.5 9-17,9-12 kurssi", CTX.year).is_err());
+
+    // times that end before they start are not a span
+    assert!(Event::from_str("    12.06 12:00-11:00 lounas", CTX.year).is_err());
+    assert!(Event::from_str("    18.-19.5 10-16,14-10 konferenssi", CTX.year).is_err());
+
+    // the hour skipped by daylight saving time moves the time forward
+    let event = Event::from_str("    31.03   03:30   p   yö", CTX.year).unwrap();
+    let expected = TZ.with_ymd_and_hms(CTX.year, 3, 31, 4, 30, 0).unwrap();
+    assert_eq!(event.date, DateVariant::DateTime(expected.fixed_offset()));
+}
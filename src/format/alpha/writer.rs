@@ -0,0 +1,298 @@
+use super::super::document::{self, Document, Item};
+use super::super::registry::Writer;
+use super::super::{DateVariant, Event, Recurrence};
+use super::{weekday_label, Tag};
+use chrono::prelude::*;
+use chrono::Duration;
+use std::io;
+
+// the widths of the `pvm`, `aika` and `tag` columns, the description takes the rest of the line
+const INDENT: &str = "    ";
+const DATE_WIDTH: usize = 8;
+const TIME_WIDTH: usize = 8;
+const TAG_WIDTH: usize = 4;
+
+/// Writes events in the alpha column layout, `pvm aika tag kuvaus`.
+///
+/// Parsing the output of `write_document` gives back the document for the events the layout can
+/// express: a description cannot start with what would parse as a time or a tag column.
+pub struct AlphaWriter;
+
+impl Writer for AlphaWriter {
+    fn name(&self) -> &str {
+        "alpha"
+    }
+
+    /// Writes the events under year lines and section headings as they change.
+    fn write(&self, events: &[Event], out: &mut dyn io::Write) -> io::Result<()> {
+        let mut year = None;
+        let mut section = None;
+        for event in events {
+            let event_year = natural_year(&event.date);
+            if year != Some(event_year) {
+                if year.is_some() {
+                    writeln!(out)?;
+                }
+                writeln!(out, "{}", event_year)?;
+                year = Some(event_year);
+                section = Some(event_year.to_string());
+            }
+            if event.section.is_some() && event.section != section {
+                writeln!(out, "### {}", event.section.as_deref().unwrap())?;
+                section = event.section.clone();
+            }
+            write_lines(&format_event(event, event_year), &event.notes, out)?;
+        }
+        Ok(())
+    }
+}
+
+impl AlphaWriter {
+    /// Writes the document with its blocks starting on the lines they were parsed from.
+    pub fn write_document(&self, document: &Document) -> String {
+        write_items(document, |event, year| {
+            let mut text = format_event(event, year);
+            for note in &event.notes {
+                text.push_str(&format!("\n{}{}* {}", INDENT, INDENT, note));
+            }
+            text
+        })
+    }
+}
+
+/// Rewrites an alpha file into the canonical layout: aligned columns, zero-padded `dd.mm` dates
+/// and `dd.mm-dd.mm` spans. Only the event lines are rewritten, keeping their line endings. The
+/// other lines, including the notes of the events, are kept byte for byte, and so are the events
+/// that would not parse back the same from the canonical layout.
+pub fn format_alpha(start_year: i32, source: &str) -> String {
+    let document = document::parse_alpha(start_year, source);
+    let mut lines = source
+        .split_inclusive('\n')
+        .map(str::to_owned)
+        .collect::<Vec<String>>();
+
+    for item in document.items() {
+        let event = match item {
+            Item::Event { event, .. } => event,
+            _ => continue,
+        };
+        let number = event.source.lines.start;
+        let year = document.year_at(number);
+        let line = format_event(event, year);
+        let same = match Event::from_str(&line, year) {
+            Ok(mut parsed) => {
+                parsed.section = event.section.clone();
+                parsed.notes = event.notes.clone();
+                parsed.source = event.source.clone();
+                parsed == *event
+            }
+            Err(_) => false,
+        };
+        if !same {
+            warn!("keeping the line of {:?} as it is", event);
+            continue;
+        }
+        let original = &lines[number];
+        let text = original.trim_end_matches(['\r', '\n']);
+        let ending = &original[text.len()..];
+        lines[number] = format!("{}{}", line, ending);
+    }
+    lines.concat()
+}
+
+/// Writes the items of the document, with `write_event` writing an event with its notes in the
+/// year of the context.
+fn write_items<F>(document: &Document, write_event: F) -> String
+where
+    F: Fn(&Event, i32) -> String,
+{
+    let mut out = String::new();
+    let mut line = 0;
+    let mut year = document.start_year;
+    for (i, block) in document.blocks.iter().enumerate() {
+        // blocks are separated by at least one blank line
+        let start = if i == 0 {
+            block.lines.start
+        } else {
+            block.lines.start.max(line + 1)
+        };
+        while line < start {
+            out.push('\n');
+            line += 1;
+        }
+
+        for item in &block.items {
+            let text = match item {
+                Item::Year { year: y, .. } => {
+                    year = *y;
+                    y.to_string()
+                }
+                Item::Section { title, level, .. } => {
+                    format!("{} {}", "#".repeat(*level), title)
+                }
+                Item::Event { event, .. } => write_event(event, year),
+                Item::Unparsed { text, .. } => text.clone(),
+            };
+            out.push_str(&text);
+            out.push('\n');
+            line += text.split('\n').count();
+        }
+    }
+    out
+}
+
+fn write_lines(line: &str, notes: &[String], out: &mut dyn io::Write) -> io::Result<()> {
+    writeln!(out, "{}", line)?;
+    for note in notes {
+        writeln!(out, "{}{}* {}", INDENT, INDENT, note)?;
+    }
+    Ok(())
+}
+
+/// The year the date of the event is written in without an explicit year.
+fn natural_year(date: &DateVariant) -> i32 {
+    match date {
+        DateVariant::Month { year, .. } | DateVariant::Year(year) => *year as i32,
+        date => date.start_date().year(),
+    }
+}
+
+/// Formats an event as a line of columns, with `year` as the year of the context.
+pub fn format_event(event: &Event, year: i32) -> String {
+    let (mut date, time) = format_date(&event.date, year, event.recurrence);
+    if event.approximate && !event.tags.contains(&Tag::Estimate) {
+        date.insert(0, '~');
+    }
+    let tags = format_tags(&event.tags, &event.reminders);
+
+    // a column starts where it would if the ones before it fit their widths
+    let mut line = match event.weekday {
+        Some(weekday) => {
+            format!(
+                "{:width$}",
+                weekday_label(weekday),
+                width = INDENT.len() - 1
+            ) + " "
+        }
+        None => INDENT.to_owned(),
+    };
+    let mut stop = INDENT.len();
+    for (column, width) in &[
+        (date, DATE_WIDTH),
+        (time.unwrap_or_default(), TIME_WIDTH),
+        (tags, TAG_WIDTH),
+    ] {
+        line.push_str(column);
+        stop += width;
+        let len = line.chars().count();
+        let padding = if len < stop { stop - len } else { 1 };
+        line.push_str(&" ".repeat(padding));
+    }
+    line.push_str(&event.description);
+    line.trim_end().to_owned()
+}
+
+/// Formats the date and the time columns.
+fn format_date(
+    date: &DateVariant,
+    year: i32,
+    recurrence: Option<Recurrence>,
+) -> (String, Option<String>) {
+    // HACK: the defaults of an open time span, see `resolve_times`
+    let default_start = NaiveTime::from_hms_opt(6, 0, 0).unwrap();
+    let default_end = NaiveTime::from_hms_opt(23, 59, 0).unwrap();
+
+    match date {
+        DateVariant::Date(date) if recurrence == Some(Recurrence::Monthly) => {
+            (format!("{:02}.xx", date.day()), None)
+        }
+        DateVariant::Date(date) => (format_day(*date, year), None),
+        DateVariant::DateTime(date) => {
+            let date = date.naive_local();
+            (
+                format_day(date.date(), year),
+                Some(format_time(date.time())),
+            )
+        }
+        DateVariant::TimeSpan(start, end) => {
+            let (start, end) = (start.naive_local(), end.naive_local());
+            if start.date() == end.date() {
+                // the end is needed to tell a span from a time
+                let start_time = Some(start.time()).filter(|time| *time != default_start);
+                let time = format!(
+                    "{}-{}",
+                    start_time.map(format_time).unwrap_or_default(),
+                    format_time(end.time())
+                );
+                (format_day(start.date(), year), Some(time))
+            } else {
+                let start_time = Some(start.time()).filter(|time| *time != default_start);
+                let end_time = Some(end.time()).filter(|time| *time != default_end);
+                let time = match (start_time, end_time) {
+                    (None, None) => None,
+                    (start_time, end_time) => Some(format!(
+                        "{}-{}",
+                        start_time.map(format_time).unwrap_or_default(),
+                        end_time.map(format_time).unwrap_or_default()
+                    )),
+                };
+                let dates = if start.year() == year && end.year() == year {
+                    format!(
+                        "{}-{}",
+                        format_day(start.date(), year),
+                        format_day(end.date(), year)
+                    )
+                } else {
+                    format!(
+                        "{}-{}",
+                        format_day_with_year(start.date()),
+                        format_day_with_year(end.date())
+                    )
+                };
+                (dates, time)
+            }
+        }
+        DateVariant::Month { month, .. } => (format!("xx.{:02}", month), None),
+        DateVariant::Year(_) => ("xx.xx".to_owned(), None),
+    }
+}
+
+fn format_day(date: NaiveDate, year: i32) -> String {
+    if date.year() == year {
+        format!("{:02}.{:02}", date.day(), date.month())
+    } else {
+        format_day_with_year(date)
+    }
+}
+
+fn format_day_with_year(date: NaiveDate) -> String {
+    format!("{:02}.{:02}.{}", date.day(), date.month(), date.year())
+}
+
+fn format_time(time: NaiveTime) -> String {
+    time.format("%H:%M").to_string()
+}
+
+/// Formats the tag column, eg. `pr!2d`.
+fn format_tags(tags: &[Tag], reminders: &[Duration]) -> String {
+    let mut column = tags.iter().map(|tag| tag.key()).collect::<String>();
+    for reminder in reminders {
+        column.push('!');
+        column.push_str(&format_reminder(*reminder));
+    }
+    column
+}
+
+/// Formats a reminder in the largest unit it is a whole number of, the inverse of `parse_offset`.
+fn format_reminder(offset: Duration) -> String {
+    let minutes = offset.num_minutes();
+    if minutes != 0 && minutes % (7 * 24 * 60) == 0 {
+        format!("{}w", minutes / (7 * 24 * 60))
+    } else if minutes != 0 && minutes % (24 * 60) == 0 {
+        format!("{}d", minutes / (24 * 60))
+    } else if minutes != 0 && minutes % 60 == 0 {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
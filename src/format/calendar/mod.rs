@@ -5,12 +5,6 @@ use super::*;
 use chrono::prelude::*;
 use std::collections::HashMap;
 
-#[derive(Debug, PartialEq)]
-pub struct Event {
-    pub date: DateVariant,
-    pub description: String,
-}
-
 enum LineType {
     Date,
     Event,
@@ -30,22 +24,45 @@ enum Context {
 }
 
 pub fn parse_calendar(source: &str, date_ctx: NaiveDate) -> Vec<Event> {
+    parse_calendar_with_context(source, &DateContext::new(date_ctx, YearPolicy::Fixed))
+}
+
+/// Parses a calendar, inferring the years of the day headers from the context. A header without
+/// a year is in the year of the header before it, or of the reference date for the first one.
+pub fn parse_calendar_with_context(source: &str, context: &DateContext) -> Vec<Event> {
     let mut events = Vec::new();
-    let mut ctx = Context::Date(date_ctx);
+    let mut ctx = Context::Date(context.reference);
+    let mut day_tags: Vec<String> = vec![];
+    // the tags of the day are on the line following the date header
+    let mut expect_day_tags = false;
+    let mut previous_date: Option<NaiveDate> = None;
+    let mut day_weekday = None;
+    // the date of the events is in the day header
+    let mut day_span = None;
 
     let lines = source.split('\n');
-    for line in lines {
+    for (number, line) in lines.enumerate() {
+        let offset = line.as_ptr() as usize - source.as_ptr() as usize;
         let mut tokens = line.split_whitespace().collect::<Vec<&str>>();
 
+        if expect_day_tags {
+            expect_day_tags = false;
+            if let Some(tags) = parse_day_tags(line) {
+                trace!("set day tags to {:?} based on \"{:?}\"", tags, line);
+                day_tags = tags;
+                continue;
+            }
+        }
+
         // linebreak: date context is no longer valid
-        if tokens.len() == 0 {
+        if tokens.is_empty() {
             match ctx {
                 Context::Date(date) => {
                     // degrade context date into a year
                     ctx = Context::Year(date.year());
                     continue;
                 }
-                Context::Year(year) => {}
+                Context::Year(_) => {}
             }
         }
 
@@ -75,59 +92,75 @@ pub fn parse_calendar(source: &str, date_ctx: NaiveDate) -> Vec<Event> {
                     Context::Year(year) => year,
                     Context::Date(date) => date.year(),
                 };
-                for token in tokens {
-                    // try parse the first or the second token into a date
-                    if let Some(date) = parse_date(token, year) {
+                // the header may name the weekday, eg. `### Pe 30.8.`, or only the weekday, which
+                // is the next such day after the previous header
+                let weekday = tokens.first().and_then(|token| alpha::parse_weekday(token));
+                let date = tokens
+                    .iter()
+                    .find_map(|token| parse_date(token, year).map(|date| (date, *token)))
+                    .map(
+                        |(date, token)| match context.rolls_over(date, previous_date) {
+                            true => (parse_date(token, year + 1).unwrap_or(date), token),
+                            false => (date, token),
+                        },
+                    );
+                let date = match date {
+                    Some(date) => Some(date),
+                    None => weekday
+                        .zip(previous_date)
+                        .map(|(weekday, after)| (alpha::next_weekday(after, weekday), tokens[0])),
+                };
+                match date {
+                    Some((date, token)) => {
+                        if let Some(weekday) = weekday.filter(|&weekday| weekday != date.weekday())
+                        {
+                            warn!(
+                                "{} is a {}, not {} as labeled in \"{}\"",
+                                date,
+                                date.weekday(),
+                                weekday,
+                                line
+                            );
+                        }
                         trace!("set date context to {:?} based on \"{:?}\"", date, line);
                         ctx = Context::Date(date);
-                        continue;
-                    } else {
-                        trace!(
-                            "ignore date candidate: \"{:?}\" because the second element is not a date",
-                            line
-                        );
-                        continue;
+                        previous_date = Some(date);
+                        day_weekday = weekday;
+                        day_span = Some(Span::of(line, token).relocate(number, offset));
+                        day_tags = vec![];
+                        expect_day_tags = true;
                     }
+                    None => trace!(
+                        "ignore date candidate: \"{:?}\" because it has no date",
+                        line
+                    ),
                 }
             }
             LineType::Event => {
                 // events only apply when there is a valid date context
                 if let Context::Date(date) = ctx {
                     let timing_candidate = tokens.remove(0);
-                    // try parse the first token into a time span
-                    if let Some((start_time, end_time)) = parse_timespan(timing_candidate) {
-                        let start_date = date.and_hms(start_time.hour(), start_time.minute(), 0);
-                        let end_date = date.and_hms(end_time.hour(), end_time.minute(), 0);
-                        let dv = DateVariant::TimeSpan(
-                            TZ.from_local_datetime(&start_date)
-                                .unwrap()
-                                .with_timezone(&Local),
-                            TZ.from_local_datetime(&end_date)
-                                .unwrap()
-                                .with_timezone(&Local),
-                        );
-
-                        let event = Event {
-                            date: dv,
-                            description: tokens.join(" "),
+                    // try parse the first token into a time or a time span
+                    let event_date = parse_time_ranges(timing_candidate)
+                        .and_then(|times| resolve_times(date, date, &times, context.timezone));
+                    if let Some(event_date) = event_date {
+                        let description = tokens.join(" ");
+                        let source = Source {
+                            lines: number..number + 1,
+                            span: Span::of(line, line.trim()).relocate(number, offset),
+                            date: day_span.clone(),
+                            ..Source::new(SourceFormat::Calendar.name())
                         };
-                        debug!("create event: {:?}", event);
-                        events.push(event);
-                    }
-                    // try parse the first token into a time
-                    if let Some(time) = parse_time(timing_candidate) {
-                        let date_time = date.and_hms(time.hour(), time.minute(), 0);
-                        let dv = DateVariant::DateTime(
-                            TZ.from_local_datetime(&date_time)
-                                .unwrap()
-                                .with_timezone(&Local),
-                        );
+                        // the tags given for the whole day, eg. `[08:30][projekti,loma]`, label
+                        // the events of the day
                         let event = Event {
-                            date: dv,
-                            description: tokens.join(" "),
+                            labels: day_tags.clone(),
+                            weekday: day_weekday,
+                            ..Event::new(event_date, &description, source)
                         };
                         debug!("create event: {:?}", event);
                         events.push(event);
+                        continue;
                     }
                     trace!(
                         "ignore line \"{:?}\" because \"{:?}\" is not a time-span nor a time",
@@ -141,3 +174,22 @@ pub fn parse_calendar(source: &str, date_ctx: NaiveDate) -> Vec<Event> {
 
     events
 }
+
+/// Parses the tags from the start-of-day line, eg. `[08:30][projekti,S,loma]`. Free-form notes in
+/// the brackets are not tags.
+fn parse_day_tags(line: &str) -> Option<Vec<String>> {
+    let line = line.trim();
+    let (_, tags) = line.strip_prefix('[')?.split_once("][")?;
+    let tags = tags.strip_suffix(']')?;
+
+    let tags = tags
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_owned)
+        .collect::<Vec<String>>();
+    if tags.iter().any(|tag| tag.contains(char::is_whitespace)) {
+        return None;
+    }
+    Some(tags)
+}
@@ -1,4 +1,5 @@
-use super::parse_calendar;
+use super::{parse_calendar, parse_calendar_with_context};
+use crate::format::{DateContext, YearPolicy};
 use chrono::prelude::*;
 
 #[test]
@@ -8,9 +9,88 @@ fn calendar_parses_correct() {
     let file_contents = include_str!("calendar.md");
 
     // feed the whole file into the library and process into DOM events
-    let entries = parse_calendar(&file_contents, NaiveDate::from_ymd(2019, 8, 23));
+    let entries = parse_calendar(file_contents, NaiveDate::from_ymd_opt(2019, 8, 23).unwrap());
 
     for entry in entries {
         println!("DOM Event: {:?}", entry);
     }
 }
+
+#[test]
+fn day_tags_apply_to_events() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let file_contents = include_str!("calendar.md");
+    let entries = parse_calendar(file_contents, NaiveDate::from_ymd_opt(2019, 8, 23).unwrap());
+
+    let danke = entries
+        .iter()
+        .find(|entry| entry.description.starts_with("danke"))
+        .unwrap();
+    assert_eq!(danke.labels, vec!["loma".to_owned()]);
+
+    // free-form notes in the brackets are not tags
+    let bofferointi = entries
+        .iter()
+        .find(|entry| entry.description.starts_with("bofferointia"))
+        .unwrap();
+    assert!(bofferointi.labels.is_empty());
+}
+
+#[test]
+fn header_weekdays_are_parsed() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let source = "### Pe 30.8.\n* 12:00 lounas\n\n### Ma\n* 9:00 palaveri\n";
+    let entries = parse_calendar(source, NaiveDate::from_ymd_opt(2019, 8, 23).unwrap());
+
+    let days = entries
+        .iter()
+        .map(|entry| (entry.date.start_date(), entry.weekday))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        days,
+        vec![
+            (
+                NaiveDate::from_ymd_opt(2019, 8, 30).unwrap(),
+                Some(Weekday::Fri)
+            ),
+            (
+                NaiveDate::from_ymd_opt(2019, 9, 2).unwrap(),
+                Some(Weekday::Mon)
+            ),
+        ]
+    );
+
+    // the date of an event comes from its day header
+    let date = entries[1].source.date.as_ref().unwrap();
+    assert_eq!(date.line, 3);
+    assert_eq!(&source[date.bytes.clone()], "Ma");
+    assert_eq!(
+        &source[entries[1].source.span.bytes.clone()],
+        "* 9:00 palaveri"
+    );
+
+    // the next Friday after a Friday is a week later
+    let source = "### Pe 30.8.\n* 12:00 lounas\n\n### Pe\n* 12:00 lounas\n";
+    let entries = parse_calendar(source, NaiveDate::from_ymd_opt(2019, 8, 23).unwrap());
+    assert_eq!(
+        entries[1].date.start_date(),
+        NaiveDate::from_ymd_opt(2019, 9, 6).unwrap()
+    );
+}
+
+#[test]
+fn headers_roll_over_into_the_next_year() {
+    let source = "### 30.12.\n* 12:00 lounas\n\n### 2.1.\n* 9:00 palaveri\n";
+    let days = |policy| {
+        let context = DateContext::new(NaiveDate::from_ymd_opt(2019, 12, 1).unwrap(), policy);
+        parse_calendar_with_context(source, &context)
+            .iter()
+            .map(|entry| entry.date.start_date().year())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(days(YearPolicy::Fixed), vec![2019, 2019]);
+    assert_eq!(days(YearPolicy::Monotonic), vec![2019, 2020]);
+    assert_eq!(days(YearPolicy::Window { months: 2 }), vec![2019, 2020]);
+}
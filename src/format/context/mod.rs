@@ -0,0 +1,81 @@
+#[cfg(test)]
+mod test;
+
+use super::alpha::ParseError;
+use super::TZ;
+use chrono::prelude::*;
+use chrono::Months;
+use chrono_tz::Tz;
+
+/// How the year of a date written without one is inferred.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum YearPolicy {
+    /// The dates are in the year of the reference date.
+    #[default]
+    Fixed,
+    /// A date earlier than the reference date by more than `months` months is in the next year,
+    /// eg. `15.01` written in December.
+    Window { months: u32 },
+    /// A date earlier than the date before it is in the next year, and so are the dates after it.
+    Monotonic,
+}
+
+impl std::str::FromStr for YearPolicy {
+    type Err = ParseError;
+
+    /// Parses a policy by its name, `fixed`, `monotonic` or `window:N` for a window of N months.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "fixed" => Ok(YearPolicy::Fixed),
+            None if s == "monotonic" => Ok(YearPolicy::Monotonic),
+            Some(("window", months)) => months
+                .parse()
+                .map(|months| YearPolicy::Window { months })
+                .map_err(|_| ParseError(format!("invalid number of months: {}", months))),
+            _ => Err(ParseError(format!("unknown year policy: {}", s))),
+        }
+    }
+}
+
+/// The date the notes are read on and how the years of their dates are inferred from it, and the
+/// time zone their times are in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DateContext {
+    /// The dates without a year are in the year of the reference date unless the policy says
+    /// otherwise.
+    pub reference: NaiveDate,
+    pub policy: YearPolicy,
+    /// Europe/Helsinki by default.
+    pub timezone: Tz,
+}
+
+impl DateContext {
+    pub fn new(reference: NaiveDate, policy: YearPolicy) -> Self {
+        DateContext {
+            reference,
+            policy,
+            timezone: TZ,
+        }
+    }
+
+    /// The dates are in `year`, as with a start year.
+    pub fn year(year: i32) -> Self {
+        DateContext::new(
+            NaiveDate::from_ymd_opt(year, 1, 1).unwrap(),
+            YearPolicy::Fixed,
+        )
+    }
+
+    /// Whether a date read in the year of the reference is in the next year instead, `previous`
+    /// being the date before it in the file.
+    pub fn rolls_over(&self, date: NaiveDate, previous: Option<NaiveDate>) -> bool {
+        match self.policy {
+            YearPolicy::Fixed => false,
+            YearPolicy::Window { months } => self
+                .reference
+                .checked_sub_months(Months::new(months))
+                .is_some_and(|earliest| date < earliest),
+            YearPolicy::Monotonic => previous.is_some_and(|previous| date < previous),
+        }
+    }
+}
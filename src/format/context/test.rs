@@ -0,0 +1,32 @@
+use super::{DateContext, YearPolicy};
+use chrono::NaiveDate;
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+#[test]
+fn policies_roll_dates_into_the_next_year() {
+    let reference = date(2019, 12, 10);
+    let window = DateContext::new(reference, YearPolicy::Window { months: 2 });
+    assert!(window.rolls_over(date(2019, 1, 15), None));
+    assert!(!window.rolls_over(date(2019, 10, 15), None));
+
+    let monotonic = DateContext::new(reference, YearPolicy::Monotonic);
+    assert!(monotonic.rolls_over(date(2019, 1, 15), Some(date(2019, 12, 30))));
+    assert!(!monotonic.rolls_over(date(2019, 1, 15), None));
+
+    assert!(!DateContext::year(2019).rolls_over(date(2019, 1, 15), Some(date(2019, 12, 30))));
+}
+
+#[test]
+fn policies_are_named() {
+    assert_eq!("fixed".parse().ok(), Some(YearPolicy::Fixed));
+    assert_eq!("monotonic".parse().ok(), Some(YearPolicy::Monotonic));
+    assert_eq!(
+        "window:3".parse().ok(),
+        Some(YearPolicy::Window { months: 3 })
+    );
+    assert!("window:x".parse::<YearPolicy>().is_err());
+    assert!("yearly".parse::<YearPolicy>().is_err());
+}
@@ -0,0 +1,56 @@
+#[cfg(test)]
+mod test;
+
+use super::Event;
+
+/// A difference between two versions of the events.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Change<'a> {
+    Removed(&'a Event),
+    Added(&'a Event),
+    /// An event with the same description has changed, eg. moved to another date.
+    Changed {
+        old: &'a Event,
+        new: &'a Event,
+    },
+}
+
+impl Change<'_> {
+    /// The newer version of the event, or the removed one.
+    pub fn event(&self) -> &Event {
+        match self {
+            Change::Removed(event) | Change::Added(event) => event,
+            Change::Changed { new, .. } => new,
+        }
+    }
+}
+
+/// The changes from the `old` events to the `new` ones, in the order of the dates.
+pub fn diff<'a>(old: &'a [Event], new: &'a [Event]) -> Vec<Change<'a>> {
+    let mut removed = old
+        .iter()
+        .filter(|event| !new.iter().any(|other| other.is_same(event)))
+        .collect::<Vec<&Event>>();
+    let added = new
+        .iter()
+        .filter(|event| !old.iter().any(|other| other.is_same(event)))
+        .collect::<Vec<&Event>>();
+
+    let mut changes = Vec::new();
+    for event in added {
+        // a removed event with the same description is the older version of the event
+        match removed
+            .iter()
+            .position(|other| other.description == event.description)
+        {
+            Some(i) => changes.push(Change::Changed {
+                old: removed.remove(i),
+                new: event,
+            }),
+            None => changes.push(Change::Added(event)),
+        }
+    }
+    changes.extend(removed.into_iter().map(Change::Removed));
+    changes.sort_by_key(|change| change.event().date.start_date());
+    changes
+}
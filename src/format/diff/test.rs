@@ -0,0 +1,30 @@
+use super::{diff, Change};
+use crate::format::document::parse_alpha;
+
+#[test]
+fn changes_are_found() {
+    let old = parse_alpha(
+        2019,
+        "    01.05   p   vappu\n    14.05       konvoluutioneuroverkot\n    20.06       juhannus\n",
+    )
+    .into_events();
+    let new = parse_alpha(
+        2019,
+        "    01.05   p   vappu\n    15.05       konvoluutioneuroverkot\n    04.06   p   tilit\n",
+    )
+    .into_events();
+
+    let changes = diff(&old, &new);
+    assert_eq!(
+        changes,
+        vec![
+            Change::Changed {
+                old: &old[1],
+                new: &new[1],
+            },
+            Change::Added(&new[2]),
+            Change::Removed(&old[2]),
+        ]
+    );
+    assert!(diff(&old, &old).is_empty());
+}
@@ -0,0 +1,291 @@
+#[cfg(test)]
+mod test;
+
+use super::{parse_date_parts, DateContext, DateVariant, Event, YearPolicy};
+use chrono::Datelike;
+use std::ops::Range;
+
+/// A parsed source file. The lines are split into blocks separated by blank lines, and each
+/// block into items. Line numbers are zero-based indices into the source.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Document {
+    /// The year in effect before the first year line.
+    pub start_year: i32,
+    pub blocks: Vec<Block>,
+}
+
+/// A run of consecutive non-blank lines.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Block {
+    pub lines: Range<usize>,
+    pub items: Vec<Item>,
+}
+
+// most of the items are events, boxing them would not save anything
+#[allow(clippy::large_enum_variant)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Clone)]
+pub enum Item {
+    /// A line with only a year, eg. `2020`, which sets the year of the dates after it. A year
+    /// line also starts a section named by the year.
+    Year { year: i32, line: usize },
+    /// A heading, eg. `### Repeating`, which starts a section.
+    Section {
+        title: String,
+        level: usize,
+        line: usize,
+    },
+    /// An event with the bullet lines following it, eg. `* kysy mitä teki kandiksi`.
+    Event { event: Event, lines: Range<usize> },
+    /// A line that is none of the above.
+    Unparsed { text: String, line: usize },
+}
+
+impl Item {
+    /// The source lines of the item.
+    pub fn lines(&self) -> Range<usize> {
+        match self {
+            Item::Year { line, .. } | Item::Section { line, .. } | Item::Unparsed { line, .. } => {
+                *line..*line + 1
+            }
+            Item::Event { lines, .. } => lines.clone(),
+        }
+    }
+}
+
+impl Document {
+    pub fn items(&self) -> impl Iterator<Item = &Item> {
+        self.blocks.iter().flat_map(|block| block.items.iter())
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = &Event> {
+        self.events_with_lines().map(|(event, _)| event)
+    }
+
+    /// The events with their source lines.
+    pub fn events_with_lines(&self) -> impl Iterator<Item = (&Event, Range<usize>)> {
+        self.items().filter_map(|item| match item {
+            Item::Event { event, lines } => Some((event, lines.clone())),
+            _ => None,
+        })
+    }
+
+    /// The titles of the sections in order, including the year sections.
+    pub fn sections(&self) -> impl Iterator<Item = String> + '_ {
+        self.items().filter_map(Item::section_title)
+    }
+
+    /// The years set by year lines in order.
+    pub fn years(&self) -> impl Iterator<Item = i32> + '_ {
+        self.items().filter_map(|item| match item {
+            Item::Year { year, .. } => Some(*year),
+            _ => None,
+        })
+    }
+
+    /// The lines that could not be parsed, with their line numbers.
+    pub fn unparsed(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.items().filter_map(|item| match item {
+            Item::Unparsed { text, line } => Some((*line, text.as_str())),
+            _ => None,
+        })
+    }
+
+    /// The item on the line, if any.
+    pub fn item_at(&self, line: usize) -> Option<&Item> {
+        self.items().find(|item| item.lines().contains(&line))
+    }
+
+    /// The event on the line, if any.
+    pub fn event_at(&self, line: usize) -> Option<&Event> {
+        match self.item_at(line)? {
+            Item::Event { event, .. } => Some(event),
+            _ => None,
+        }
+    }
+
+    /// The year in effect on the line.
+    pub fn year_at(&self, line: usize) -> i32 {
+        self.items()
+            .take_while(|item| item.lines().start <= line)
+            .filter_map(|item| match item {
+                Item::Year { year, .. } => Some(*year),
+                _ => None,
+            })
+            .last()
+            .unwrap_or(self.start_year)
+    }
+
+    /// The title of the section the line is in, if any.
+    pub fn section_at(&self, line: usize) -> Option<String> {
+        self.items()
+            .take_while(|item| item.lines().start <= line)
+            .filter_map(Item::section_title)
+            .last()
+    }
+
+    /// Creates a document of events parsed from a format without blocks, one block per event.
+    pub fn from_events(start_year: i32, events: Vec<Event>) -> Document {
+        let blocks = events
+            .into_iter()
+            .map(|event| {
+                let lines = event.source.lines.clone();
+                Block {
+                    lines: lines.clone(),
+                    items: vec![Item::Event { event, lines }],
+                }
+            })
+            .collect();
+        Document { start_year, blocks }
+    }
+
+    /// Takes the events out of the document.
+    pub fn into_events(self) -> Vec<Event> {
+        self.blocks
+            .into_iter()
+            .flat_map(|block| block.items)
+            .filter_map(|item| match item {
+                Item::Event { event, .. } => Some(event),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Item {
+    /// The title of the section the item starts, if any.
+    fn section_title(&self) -> Option<String> {
+        match self {
+            Item::Year { year, .. } => Some(year.to_string()),
+            Item::Section { title, .. } => Some(title.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Parses an alpha file, with `start_year` as the year of the dates before the first year line.
+pub fn parse_alpha(start_year: i32, source: &str) -> Document {
+    parse_alpha_with_context(&DateContext::year(start_year), source)
+}
+
+/// Parses an alpha file, inferring the years of the dates before the first year line from the
+/// context. The year lines set the year of the dates after them as they are.
+pub fn parse_alpha_with_context(context: &DateContext, source: &str) -> Document {
+    let start_year = context.reference.year();
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut block: Option<Block> = None;
+    let mut cur_year = start_year;
+    // the years are inferred until the first year line, from the dates in order
+    let mut inferred = true;
+    let mut previous_ordered = None;
+    let mut cur_section = None;
+    // a weekday with a time in place of the date is the next such day after the previous event
+    let mut previous_date = None;
+
+    for (number, line) in source.split('\n').enumerate() {
+        if line.trim().is_empty() {
+            blocks.extend(block.take());
+            continue;
+        }
+        let block = block.get_or_insert_with(|| Block {
+            lines: number..number,
+            items: vec![],
+        });
+        block.lines.end = number + 1;
+
+        // bullet lines following an event are notes of it
+        if let Some(Item::Event { event, lines }) = block.items.last_mut() {
+            if let Some(note) = bullet(line).filter(|_| lines.end == number) {
+                event.notes.push(note.to_owned());
+                lines.end = number + 1;
+                event.source.lines = lines.clone();
+                continue;
+            }
+        }
+
+        let parsed =
+            Event::from_str_after(line, cur_year, previous_date, context.timezone).map(|event| {
+                // repeating and imprecise dates are in no order to infer the year from
+                let precise =
+                    !matches!(event.date, DateVariant::Month { .. } | DateVariant::Year(_));
+                // nor is there a year to infer when the date has one
+                let explicit = event
+                    .source
+                    .date
+                    .as_ref()
+                    .is_some_and(|span| has_year(&line[span.bytes.clone()]));
+                if !(inferred && precise && event.recurrence.is_none()) || explicit {
+                    return event;
+                }
+                let date = event.date.start_date();
+                if !context.rolls_over(date, previous_ordered) {
+                    previous_ordered = Some(date);
+                    return event;
+                }
+                debug!("{} rolls over to the next year", event.date);
+                let year = cur_year + 1;
+                // the dates after a monotonic roll over are in the next year too
+                if context.policy == YearPolicy::Monotonic {
+                    cur_year = year;
+                }
+                let event = Event::from_str_after(line, year, previous_date, context.timezone)
+                    .unwrap_or(event);
+                previous_ordered = Some(event.date.start_date());
+                event
+            });
+        let item = if let Ok(mut event) = parsed {
+            previous_date = Some(event.date.start_date());
+            event.section = cur_section.clone();
+            let offset = line.as_ptr() as usize - source.as_ptr() as usize;
+            event.source.relocate(number, offset);
+            Item::Event {
+                event,
+                lines: number..number + 1,
+            }
+        } else if let Ok(year) = line.trim().parse::<i32>() {
+            debug!("context changes year: {}", year);
+            cur_year = year;
+            inferred = false;
+            cur_section = Some(year.to_string());
+            Item::Year { year, line: number }
+        } else if line.trim_start().starts_with('#') {
+            let heading = line.trim();
+            let title = heading.trim_start_matches('#').trim();
+            debug!("context changes section: {}", title);
+            cur_section = Some(title.to_owned());
+            Item::Section {
+                title: title.to_owned(),
+                level: heading.len() - heading.trim_start_matches('#').len(),
+                line: number,
+            }
+        } else {
+            debug!("unparsed line {}", line);
+            Item::Unparsed {
+                text: line.to_owned(),
+                line: number,
+            }
+        };
+        block.items.push(item);
+    }
+    blocks.extend(block);
+
+    Document { start_year, blocks }
+}
+
+/// The date token names a year, eg. `01.03.2019` or `30.12-2.1.2020`.
+fn has_year(token: &str) -> bool {
+    let token = token.trim_start_matches('~');
+    std::iter::once(token)
+        .chain(token.split('-'))
+        .any(|date| parse_date_parts(date).is_some_and(|(_, _, year)| year.is_some()))
+}
+
+/// The text of a bullet line, eg. `kysy` for `    * kysy`.
+fn bullet(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    line.strip_prefix("* ")
+        .or_else(|| line.strip_prefix("- "))
+        .map(str::trim)
+}
@@ -0,0 +1,182 @@
+use super::{parse_alpha, parse_alpha_with_context, Item};
+use crate::format::{DateContext, DateVariant, YearPolicy};
+use chrono::prelude::*;
+
+const SOURCE: &str = "\
+ALPHA-SELF {
+    01.05   p   vappu
+    14.05       konvoluutioneuroverkot
+        * kysy mitä teki kandiksi
+        * lähetä diplomityö luettavaksi
+
+### Repeating
+    04.06   p   tilit
+
+\t2020
+    05.06       vuokra
+}";
+
+#[test]
+fn document_has_blocks_and_items() {
+    let document = parse_alpha(2019, SOURCE);
+
+    let lines = document
+        .blocks
+        .iter()
+        .map(|block| block.lines.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(lines, vec![0..5, 6..8, 9..12]);
+
+    assert_eq!(
+        document.blocks[1].items[0],
+        Item::Section {
+            title: "Repeating".to_owned(),
+            level: 3,
+            line: 6,
+        }
+    );
+    assert_eq!(document.years().collect::<Vec<i32>>(), vec![2020]);
+    assert_eq!(
+        document.sections().collect::<Vec<String>>(),
+        vec!["Repeating", "2020"]
+    );
+    assert_eq!(
+        document.unparsed().collect::<Vec<(usize, &str)>>(),
+        vec![(0, "ALPHA-SELF {"), (11, "}")]
+    );
+}
+
+#[test]
+fn document_lookups() {
+    let document = parse_alpha(2019, SOURCE);
+
+    let spans = document
+        .events_with_lines()
+        .map(|(event, lines)| (event.description.as_str(), lines))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        vec![
+            ("vappu", 1..2),
+            ("konvoluutioneuroverkot", 2..5),
+            ("tilit", 7..8),
+            ("vuokra", 10..11),
+        ]
+    );
+
+    // the notes of an event belong to it
+    let event = document.event_at(3).unwrap();
+    assert_eq!(event.description, "konvoluutioneuroverkot");
+    assert_eq!(
+        event.notes,
+        vec!["kysy mitä teki kandiksi", "lähetä diplomityö luettavaksi"]
+    );
+    assert_eq!(event.source.lines, 2..5);
+    assert_eq!(document.event_at(6), None);
+
+    assert_eq!(document.year_at(1), 2019);
+    assert_eq!(document.year_at(10), 2020);
+    assert_eq!(document.section_at(1), None);
+    assert_eq!(document.section_at(7).as_deref(), Some("Repeating"));
+    assert_eq!(
+        document
+            .event_at(10)
+            .and_then(|event| event.section.as_deref()),
+        Some("2020")
+    );
+}
+
+#[test]
+fn weekday_follows_the_previous_event() {
+    let document = parse_alpha(
+        2019,
+        "    ti 23.04       kaljaa\n    pe 18:00   p   sauna\n",
+    );
+
+    let dates = document
+        .events()
+        .map(|event| event.date.start_date())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        dates,
+        vec![
+            NaiveDate::from_ymd_opt(2019, 4, 23).unwrap(),
+            NaiveDate::from_ymd_opt(2019, 4, 26).unwrap(),
+        ]
+    );
+}
+
+#[test]
+fn years_are_inferred_until_a_year_line() {
+    let source = "    20.12       joulujuhla
+    15.01       tilit
+    xx.02       Vuokra
+    25.01       kaljaa
+2018
+    05.01       vanha
+";
+    let years = |policy| {
+        let context = DateContext::new(NaiveDate::from_ymd_opt(2019, 12, 10).unwrap(), policy);
+        parse_alpha_with_context(&context, source)
+            .events()
+            .map(|event| event.date.start_date().year())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(years(YearPolicy::Fixed), vec![2019, 2019, 2019, 2019, 2018]);
+    assert_eq!(
+        years(YearPolicy::Window { months: 1 }),
+        vec![2019, 2020, 2019, 2020, 2018]
+    );
+    // the dates after a roll over are in the next year, but a month is in no order
+    assert_eq!(
+        years(YearPolicy::Monotonic),
+        vec![2019, 2020, 2020, 2020, 2018]
+    );
+
+    // a date with a year does not roll the others over
+    let source = "    01.06       a\n    01.03.2019  b\n    02.06       c\n";
+    let context = DateContext::new(
+        NaiveDate::from_ymd_opt(2019, 1, 1).unwrap(),
+        YearPolicy::Monotonic,
+    );
+    let years = parse_alpha_with_context(&context, source)
+        .events()
+        .map(|event| event.date.start_date().year())
+        .collect::<Vec<_>>();
+    assert_eq!(years, vec![2019, 2019, 2019]);
+}
+
+#[test]
+fn spans_are_in_the_source() {
+    let source = "päivä\n    01.05   p   vappu\n";
+    let document = parse_alpha(2019, source);
+
+    let event = document.events().next().unwrap();
+    let date = event.source.date.as_ref().unwrap();
+    assert_eq!(date.line, 1);
+    assert_eq!(&source[date.bytes.clone()], "01.05");
+    assert_eq!(date.columns, 4..9);
+    assert_eq!(&source[event.source.tags[0].bytes.clone()], "p");
+    assert_eq!(
+        &source[event.source.span.bytes.clone()],
+        "01.05   p   vappu"
+    );
+}
+
+#[test]
+fn times_are_in_the_time_zone_of_the_context() {
+    let context = DateContext {
+        timezone: chrono_tz::Europe::London,
+        ..DateContext::year(2019)
+    };
+    let document = parse_alpha_with_context(&context, "    01.05   12:00   p   vappu\n");
+
+    let event = document.events().next().unwrap();
+    assert_eq!(event.date.to_string(), "Wed 2019-05-01 12:00");
+    let utc = NaiveDate::from_ymd_opt(2019, 5, 1)
+        .unwrap()
+        .and_hms_opt(11, 0, 0)
+        .unwrap();
+    assert!(matches!(&event.date, DateVariant::DateTime(date) if date.naive_utc() == utc));
+}
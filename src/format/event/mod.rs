@@ -0,0 +1,192 @@
+use super::alpha::Tag;
+use super::inline::Inline;
+use super::DateVariant;
+use chrono::{Duration, Weekday};
+use std::ops::Range;
+
+/// The built-in formats events are parsed from. Other formats can be added as a `Parser`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum SourceFormat {
+    Alpha,
+    Calendar,
+}
+
+impl SourceFormat {
+    /// The name of the format, as used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            SourceFormat::Alpha => "alpha",
+            SourceFormat::Calendar => "calendar",
+        }
+    }
+}
+
+/// Where an event was parsed from. Line numbers are zero-based indices into the source.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Source {
+    /// The name of the format, eg. `alpha`.
+    pub format: String,
+    /// The file the event was read from, when the events of several files are merged.
+    pub file: Option<String>,
+    pub lines: Range<usize>,
+    /// The text of the event on its first line.
+    pub span: Span,
+    /// The date and time tokens, eg. `18.-19.5 11-,-19`, or the day header they come from.
+    pub date: Option<Span>,
+    /// The keys of the tags, in the order of `Event::tags`.
+    pub tags: Vec<Span>,
+}
+
+impl Source {
+    /// The first line of a source in the format, with empty spans.
+    pub fn new(format: &str) -> Self {
+        Source {
+            format: format.to_owned(),
+            file: None,
+            lines: 0..1,
+            span: Span::default(),
+            date: None,
+            tags: vec![],
+        }
+    }
+
+    /// Moves the spans parsed from a single line to the zero-based `line` starting at the byte
+    /// `offset` of the source.
+    pub fn relocate(&mut self, line: usize, offset: usize) {
+        self.lines = line..line + self.lines.len();
+        for span in std::iter::once(&mut self.span)
+            .chain(self.date.as_mut())
+            .chain(self.tags.iter_mut())
+        {
+            *span = span.clone().relocate(line, offset);
+        }
+    }
+}
+
+/// A piece of text on a line of the source. Lines are zero-based, bytes are offsets into the
+/// whole source and columns count the characters from the start of the line.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Span {
+    pub line: usize,
+    pub bytes: Range<usize>,
+    pub columns: Range<usize>,
+}
+
+impl Span {
+    /// The span of `part`, a slice of `line`, with `line` as the first line of the source.
+    pub fn of(line: &str, part: &str) -> Span {
+        let start = part.as_ptr() as usize - line.as_ptr() as usize;
+        assert!(start + part.len() <= line.len(), "not a slice of the line");
+        Span::from_bytes(line, start..start + part.len())
+    }
+
+    /// The span of the bytes of `line`, with `line` as the first line of the source.
+    pub fn from_bytes(line: &str, bytes: Range<usize>) -> Span {
+        let columns = line[..bytes.start].chars().count()..line[..bytes.end].chars().count();
+        Span {
+            line: 0,
+            bytes,
+            columns,
+        }
+    }
+
+    /// The span from the start of this one to the end of `other` on the same line.
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            line: self.line,
+            bytes: self.bytes.start..other.bytes.end,
+            columns: self.columns.start..other.columns.end,
+        }
+    }
+
+    /// Moves the span to the zero-based `line` starting at the byte `offset` of the source.
+    pub fn relocate(self, line: usize, offset: usize) -> Span {
+        Span {
+            line: self.line + line,
+            bytes: self.bytes.start + offset..self.bytes.end + offset,
+            columns: self.columns,
+        }
+    }
+}
+
+/// How often an event repeats.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Recurrence {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// An event as produced by every parser and consumed by every writer.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Event {
+    pub date: DateVariant,
+    /// The tags from the legend of the alpha file.
+    pub tags: Vec<Tag>,
+    pub description: String,
+    /// The description split into text and links.
+    pub inline: Vec<Inline>,
+    /// The date or time is only an estimate, eg. marked with `~`.
+    pub approximate: bool,
+    /// How long before the event to remind of it, eg. `!2d` in the tag column.
+    pub reminders: Vec<Duration>,
+    /// The heading or the year block the event is under, eg. `Repeating` for `### Repeating`.
+    pub section: Option<String>,
+    /// Free-form names for grouping the event, eg. the day tags of a calendar.
+    pub labels: Vec<String>,
+    /// The weekday labeled before the date, eg. `ma`.
+    pub weekday: Option<Weekday>,
+    /// Where the event takes place, eg. `Hervanta` for `(@Hervanta)`.
+    pub location: Option<String>,
+    /// Lines of notes following the event, eg. `* skip next`.
+    pub notes: Vec<String>,
+    pub recurrence: Option<Recurrence>,
+    pub source: Source,
+}
+
+impl Event {
+    /// An event with only the date, the description and the source, and nothing else known of it.
+    pub fn new(date: DateVariant, description: &str, source: Source) -> Self {
+        Event {
+            date,
+            tags: vec![],
+            description: description.to_owned(),
+            inline: super::inline::parse_inline(description),
+            approximate: false,
+            reminders: vec![],
+            section: None,
+            labels: vec![],
+            weekday: None,
+            location: None,
+            notes: vec![],
+            recurrence: None,
+            source,
+        }
+    }
+
+    /// The same entry wherever it was read from: the date, the description, the tags, the
+    /// location and the recurrence are the same.
+    pub fn is_same(&self, other: &Event) -> bool {
+        self.date == other.date
+            && self.description == other.description
+            && self.tags == other.tags
+            && self.location == other.location
+            && self.recurrence == other.recurrence
+    }
+
+    /// Names for grouping events: the tags, the section and the labels of the event.
+    pub fn categories(&self) -> Vec<String> {
+        self.tags
+            .iter()
+            .map(|tag| tag.name().to_owned())
+            .chain(self.section.clone())
+            .chain(self.labels.iter().cloned())
+            .collect()
+    }
+}
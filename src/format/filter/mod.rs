@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod test;
+
+use super::*;
+
+/// Selects the events to output. Filters compose with `and`, `or` and `not`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Filter {
+    /// Every event.
+    All,
+    /// Events with the tag.
+    Tag(Tag),
+    /// Events on any day from `from` to `until`, both inclusive. An open end is unbounded.
+    DateRange {
+        from: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+    },
+    /// Events whose description contains the text, ignoring case.
+    Text(String),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Default for Filter {
+    /// Only the events tagged to be published, `p`.
+    fn default() -> Self {
+        Filter::Tag(Tag::PublishToIcs)
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, entry: &Event) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Tag(tag) => entry.tags.contains(tag),
+            Filter::DateRange { from, until } => {
+                let date = &entry.date;
+                from.is_none_or(|from| date.end_date() >= from)
+                    && until.is_none_or(|until| date.start_date() <= until)
+            }
+            Filter::Text(text) => entry
+                .description
+                .to_lowercase()
+                .contains(&text.to_lowercase()),
+            Filter::And(filters) => filters.iter().all(|filter| filter.matches(entry)),
+            Filter::Or(filters) => filters.iter().any(|filter| filter.matches(entry)),
+            Filter::Not(filter) => !filter.matches(entry),
+        }
+    }
+
+    pub fn and(self, other: Filter) -> Filter {
+        match self {
+            Filter::And(mut filters) => {
+                filters.push(other);
+                Filter::And(filters)
+            }
+            filter => Filter::And(vec![filter, other]),
+        }
+    }
+
+    pub fn or(self, other: Filter) -> Filter {
+        match self {
+            Filter::Or(mut filters) => {
+                filters.push(other);
+                Filter::Or(filters)
+            }
+            filter => Filter::Or(vec![filter, other]),
+        }
+    }
+}
+
+impl std::ops::Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        Filter::Not(Box::new(self))
+    }
+}
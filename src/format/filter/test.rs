@@ -0,0 +1,38 @@
+use super::Filter;
+use crate::format::{alpha::Tag, Event};
+use chrono::NaiveDate;
+
+fn event(line: &str) -> Event {
+    Event::from_str(line, 2019).unwrap()
+}
+
+#[test]
+fn filters_select_events() {
+    let published = event("    01.05   p   vappu");
+    let absent = event("    04.06   pX  tilit");
+    let untagged = event("    05.06-07.06   Vuokra");
+
+    assert!(Filter::default().matches(&published));
+    assert!(!Filter::default().matches(&untagged));
+    assert!(Filter::All.matches(&untagged));
+
+    let not_absent = !Filter::Tag(Tag::CannotAttend);
+    assert!(Filter::default()
+        .and(not_absent.clone())
+        .matches(&published));
+    assert!(!Filter::default().and(not_absent).matches(&absent));
+
+    let june = Filter::DateRange {
+        from: NaiveDate::from_ymd_opt(2019, 6, 7),
+        until: None,
+    };
+    assert!(june.matches(&untagged));
+    assert!(!june.matches(&absent));
+
+    let text = Filter::Text("VUOKRA".to_owned());
+    assert!(text
+        .clone()
+        .or(Filter::Tag(Tag::PublishToIcs))
+        .matches(&untagged));
+    assert!(text.or(Filter::Tag(Tag::PublishToIcs)).matches(&published));
+}
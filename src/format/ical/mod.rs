@@ -0,0 +1,401 @@
+use super::filter::Filter;
+use super::registry::Writer;
+use super::*;
+use ics::components::Property;
+use ics::properties::{
+    Categories, Comment, Description, DtEnd, DtStart, Due, Location, RRule, RelatedTo, Status,
+    Summary, Trigger, URL,
+};
+use ics::{escape_text, Alarm, ICalendar};
+use std::collections::HashMap;
+use std::io;
+use uuid::Uuid;
+
+/// Options for converting events into .ics.
+#[derive(Debug, Clone)]
+pub struct IcsConfig {
+    /// Which events are written, by default the ones tagged `p`.
+    pub filter: Filter,
+    /// Mark approximate events with `STATUS:TENTATIVE`.
+    pub approximate_tentative: bool,
+    /// Prepended to the summary of approximate events.
+    pub approximate_prefix: String,
+    /// Create linked events for the dates referenced in the text of an event, eg. `(DL: 1.3.)`.
+    pub extract_references: bool,
+    /// Reminders added to every event with the tag.
+    pub tag_reminders: HashMap<Tag, Duration>,
+    /// Which entries are written as todos instead of events.
+    pub todo_policy: TodoPolicy,
+    /// Renames categories. A category renamed to an empty string is left out.
+    pub category_map: HashMap<String, String>,
+    /// The time zone the calendar is shown in. The times are written in UTC.
+    pub timezone: Tz,
+}
+
+impl Default for IcsConfig {
+    fn default() -> Self {
+        IcsConfig {
+            filter: Filter::default(),
+            approximate_tentative: true,
+            approximate_prefix: "[~] ".to_owned(),
+            extract_references: false,
+            tag_reminders: hashmap! {
+                Tag::HandleInAdvance => Duration::days(2),
+            },
+            todo_policy: TodoPolicy::default(),
+            category_map: hashmap! {
+                // every published event has this one
+                Tag::PublishToIcs.name().to_owned() => String::new(),
+            },
+            timezone: TZ,
+        }
+    }
+}
+
+/// Decides whether an entry is written as a VTODO instead of a VEVENT.
+#[derive(Debug, Clone)]
+pub struct TodoPolicy {
+    /// Entries with any of these tags are todos.
+    pub tags: Vec<Tag>,
+    /// Entries dated only by a month or a year, eg. `xx.07`, are todos.
+    pub imprecise_dates: bool,
+}
+
+impl Default for TodoPolicy {
+    fn default() -> Self {
+        TodoPolicy {
+            tags: vec![Tag::Deadline],
+            imprecise_dates: true,
+        }
+    }
+}
+
+impl TodoPolicy {
+    pub fn is_todo(&self, entry: &Event) -> bool {
+        let imprecise = matches!(entry.date, DateVariant::Month { .. } | DateVariant::Year(_));
+        (self.imprecise_dates && imprecise) || entry.tags.iter().any(|tag| self.tags.contains(tag))
+    }
+}
+
+/// Writes the events into a calendar. The events are written as given, the filter of the
+/// configuration is left for the caller to apply.
+#[derive(Debug, Clone, Default)]
+pub struct IcsWriter {
+    pub config: IcsConfig,
+}
+
+impl Writer for IcsWriter {
+    fn name(&self) -> &str {
+        "ics"
+    }
+
+    fn write(&self, events: &[Event], out: &mut dyn io::Write) -> io::Result<()> {
+        let mut calendar = ICalendar::new("2.0", "memoparsa");
+        add_events(&mut calendar, events, &self.config);
+        calendar.write(out)
+    }
+}
+
+/// Adds the time zone hint and the events to the calendar, each event either as a todo or as events
+/// by the todo policy.
+pub fn add_events<'a, I>(calendar: &mut ICalendar<'a>, events: I, config: &IcsConfig)
+where
+    I: IntoIterator<Item = &'a Event>,
+{
+    // the times are in UTC, the time zone is a hint for showing them
+    calendar.push(Property::new("X-WR-TIMEZONE", config.timezone.name()));
+
+    for entry in events {
+        if config.todo_policy.is_todo(entry) {
+            let (todo, events) = entry.create_ics_todo_and_events_with(config);
+            calendar.add_todo(todo);
+            for event in events {
+                calendar.add_event(event);
+            }
+        } else {
+            for event in entry.create_ics_events_with(config) {
+                calendar.add_event(event);
+            }
+        }
+    }
+}
+
+pub trait CreateIcsEvent {
+    fn create_ics_event_with<'a>(&'a self, config: &IcsConfig) -> ics::Event<'a>;
+
+    fn create_ics_event<'a>(&'a self) -> ics::Event<'a> {
+        self.create_ics_event_with(&IcsConfig::default())
+    }
+
+    /// Creates the event followed by the events linked to it, such as the deadlines referenced in
+    /// its text and notes when `config.extract_references` is set.
+    fn create_ics_events_with<'a>(&'a self, config: &IcsConfig) -> Vec<ics::Event<'a>>;
+}
+
+pub trait CreateIcsTodo {
+    fn create_ics_todo_with<'a>(&'a self, config: &IcsConfig) -> ics::ToDo<'a>;
+
+    fn create_ics_todo<'a>(&'a self) -> ics::ToDo<'a> {
+        self.create_ics_todo_with(&IcsConfig::default())
+    }
+
+    /// Creates the todo and the events linked to it, such as the deadlines referenced in its text
+    /// and notes when `config.extract_references` is set.
+    fn create_ics_todo_and_events_with<'a>(
+        &'a self,
+        config: &IcsConfig,
+    ) -> (ics::ToDo<'a>, Vec<ics::Event<'a>>);
+}
+
+impl CreateIcsEvent for Event {
+    fn create_ics_event_with<'a>(&'a self, config: &IcsConfig) -> ics::Event<'a> {
+        create_ics_event(self, Uuid::new_v4().to_string(), config)
+    }
+
+    fn create_ics_events_with<'a>(&'a self, config: &IcsConfig) -> Vec<ics::Event<'a>> {
+        let uid = Uuid::new_v4().to_string();
+        let mut events = vec![create_ics_event(self, uid.clone(), config)];
+
+        events.extend(referenced_events(self, &uid, config));
+        events
+    }
+}
+
+/// Creates the events for the dates referenced in the text and the notes of the entry, such as
+/// `(DL: 1.3.2020)` or a note `2.9.: ilmo päättyy`, each related to the item `uid`, when
+/// `config.extract_references` is set.
+fn referenced_events<'a>(entry: &Event, uid: &str, config: &IcsConfig) -> Vec<ics::Event<'a>> {
+    if !config.extract_references {
+        return vec![];
+    }
+    let summary = inline::render_summary(&entry.inline);
+    let after = entry.date.start_date();
+    let references = std::iter::once(&entry.description)
+        .chain(&entry.notes)
+        .flat_map(|text| reference::extract_references(text, after));
+
+    let mut events = Vec::new();
+    for reference in references {
+        debug!("create event for {:?} in \"{}\"", reference, summary);
+        let mut event = ics::Event::new(Uuid::new_v4().to_string(), dtstamp());
+        event.push(DtStart::new(reference.date.format(DATE_FORMAT).to_string()));
+        if reference.label.is_empty() {
+            event.push(Summary::new(escape_text(summary.clone())));
+        } else {
+            event.push(Summary::new(escape_text(format!(
+                "{}: {}",
+                reference.label, summary
+            ))));
+        }
+        event.push(RelatedTo::new(uid.to_owned()));
+        event.push(Comment::new("created with memoparsa"));
+        events.push(event);
+    }
+    events
+}
+
+impl CreateIcsTodo for Event {
+    fn create_ics_todo_with<'a>(&'a self, config: &IcsConfig) -> ics::ToDo<'a> {
+        create_ics_todo(self, Uuid::new_v4().to_string(), config)
+    }
+
+    fn create_ics_todo_and_events_with<'a>(
+        &'a self,
+        config: &IcsConfig,
+    ) -> (ics::ToDo<'a>, Vec<ics::Event<'a>>) {
+        let uid = Uuid::new_v4().to_string();
+        let events = referenced_events(self, &uid, config);
+        (create_ics_todo(self, uid, config), events)
+    }
+}
+
+fn create_ics_todo<'a>(entry: &'a Event, uid: String, config: &IcsConfig) -> ics::ToDo<'a> {
+    let mut todo = ics::ToDo::new(uid, dtstamp());
+    match &entry.date {
+        DateVariant::TimeSpan(start, end) => {
+            todo.push(DtStart::new(format_datetime(start)));
+            todo.push(Due::new(format_datetime(end)));
+        }
+        DateVariant::DateTime(date) => {
+            todo.push(Due::new(format_datetime(date)));
+        }
+        DateVariant::Date(date) => {
+            todo.push(Due::new(date.format(DATE_FORMAT).to_string()));
+        }
+        date @ DateVariant::Month { .. } | date @ DateVariant::Year(_) => {
+            todo.push(DtStart::new(
+                date.start_date().format(DATE_FORMAT).to_string(),
+            ));
+            todo.push(Due::new(date.end_date().format(DATE_FORMAT).to_string()));
+        }
+    }
+    todo.push(Status::needs_action());
+
+    let (properties, alarms) = describe(entry, config);
+    for property in properties {
+        todo.push(property);
+    }
+    for alarm in alarms {
+        todo.add_alarm(alarm);
+    }
+    todo
+}
+
+fn create_ics_event<'a>(entry: &'a Event, uid: String, config: &IcsConfig) -> ics::Event<'a> {
+    let mut event = ics::Event::new(uid, dtstamp());
+    match &entry.date {
+        DateVariant::TimeSpan(start, end) => {
+            event.push(DtStart::new(format_datetime(start)));
+            event.push(DtEnd::new(format_datetime(end)));
+        }
+        DateVariant::DateTime(date) => {
+            event.push(DtStart::new(format_datetime(date)));
+        }
+        DateVariant::Date(date) => {
+            let date_fmt = date.format(DATE_FORMAT);
+            let date_str = date_fmt.to_string();
+            event.push(DtStart::new(date_str));
+        }
+        // an all-day event over the whole month or year, the end date is exclusive
+        date @ DateVariant::Month { .. } | date @ DateVariant::Year(_) => {
+            let end = date.end_date().succ_opt().unwrap();
+            event.push(DtStart::new(
+                date.start_date().format(DATE_FORMAT).to_string(),
+            ));
+            event.push(DtEnd::new(end.format(DATE_FORMAT).to_string()));
+        }
+    }
+    if entry.approximate && config.approximate_tentative {
+        event.push(Status::tentative());
+    }
+
+    let (properties, alarms) = describe(entry, config);
+    for property in properties {
+        event.push(property);
+    }
+    for alarm in alarms {
+        event.add_alarm(alarm);
+    }
+    event
+}
+
+/// Creates the properties and alarms shared by events and todos.
+fn describe<'a>(entry: &'a Event, config: &IcsConfig) -> (Vec<Property<'a>>, Vec<Alarm<'a>>) {
+    let mut properties: Vec<Property> = Vec::new();
+
+    let mut summary = inline::render_summary(&entry.inline);
+    if entry.approximate {
+        summary.insert_str(0, &config.approximate_prefix);
+    }
+    properties.push(Summary::new(escape_text(summary)).into());
+    if let Some(url) = inline::first_url(&entry.inline) {
+        properties.push(URL::new(url).into());
+    }
+    let mut notes = inline::render_notes(&entry.inline);
+    for note in &entry.notes {
+        if !notes.is_empty() {
+            notes.push('\n');
+        }
+        notes.push_str(note);
+    }
+    if !notes.is_empty() {
+        properties.push(Description::new(escape_text(notes)).into());
+    }
+    if let Some(location) = &entry.location {
+        properties.push(Location::new(escape_text(location.clone())).into());
+    }
+    if let Some(recurrence) = entry.recurrence {
+        properties.push(RRule::new(format_recurrence(recurrence)).into());
+    }
+    let categories = map_categories(entry.categories(), &config.category_map);
+    if !categories.is_empty() {
+        let categories = categories
+            .into_iter()
+            .map(|category| escape_text(category).into_owned())
+            .collect::<Vec<String>>();
+        properties.push(Categories::new(categories.join(",")).into());
+    }
+    // the file of merged events, eg. `alpha2.md:12`
+    if let Some(file) = &entry.source.file {
+        properties.push(Property::new(
+            "X-MEMOPARSA-SOURCE",
+            format!("{}:{}", file, entry.source.lines.start + 1),
+        ));
+    }
+    properties.push(Comment::new("created with memoparsa").into());
+
+    let mut reminders = entry.reminders.clone();
+    reminders.extend(
+        entry
+            .tags
+            .iter()
+            .filter_map(|tag| config.tag_reminders.get(tag)),
+    );
+    reminders.sort();
+    reminders.dedup();
+    let alarms = reminders
+        .into_iter()
+        .map(|reminder| {
+            let trigger = Trigger::new(format_offset(-reminder));
+            let description = Description::new(escape_text(inline::render_summary(&entry.inline)));
+            Alarm::display(trigger, description)
+        })
+        .collect();
+
+    (properties, alarms)
+}
+
+/// Renames the categories by `map`, leaving out the empty and the repeated ones.
+pub(crate) fn map_categories(
+    categories: Vec<String>,
+    map: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut mapped: Vec<String> = Vec::new();
+    for category in categories {
+        let category = map.get(&category).cloned().unwrap_or(category);
+        if !category.is_empty() && !mapped.contains(&category) {
+            mapped.push(category);
+        }
+    }
+    mapped
+}
+
+/// Formats a recurrence as an iCalendar recurrence rule, eg. `FREQ=MONTHLY`.
+fn format_recurrence(recurrence: Recurrence) -> &'static str {
+    match recurrence {
+        Recurrence::Daily => "FREQ=DAILY",
+        Recurrence::Weekly => "FREQ=WEEKLY",
+        Recurrence::Monthly => "FREQ=MONTHLY",
+        Recurrence::Yearly => "FREQ=YEARLY",
+    }
+}
+
+fn format_datetime(date: &DateTime<FixedOffset>) -> String {
+    date.with_timezone(&Utc).format(DATETIME_FORMAT).to_string()
+}
+
+fn dtstamp() -> String {
+    Utc::now().format(DATETIME_FORMAT).to_string()
+}
+
+/// Formats an offset as an iCalendar duration, eg. `-P2D` or `-PT1H30M`.
+pub fn format_offset(offset: Duration) -> String {
+    let sign = if offset < Duration::zero() { "-" } else { "" };
+    let minutes = offset.num_minutes().abs();
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+
+    let mut s = format!("{}P", sign);
+    if days > 0 {
+        s.push_str(&format!("{}D", days));
+    }
+    if hours > 0 || minutes > 0 || days == 0 {
+        s.push('T');
+        if hours > 0 {
+            s.push_str(&format!("{}H", hours));
+        }
+        if minutes > 0 || hours == 0 {
+            s.push_str(&format!("{}M", minutes));
+        }
+    }
+    s
+}
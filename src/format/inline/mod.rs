@@ -0,0 +1,135 @@
+#[cfg(test)]
+mod test;
+
+/// A piece of an event description, either plain text or a markdown-style link.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Clone)]
+pub enum Inline {
+    Text(String),
+    /// `[label](target)`, where the target may be an URL or just free-form text.
+    Link {
+        label: String,
+        target: String,
+    },
+}
+
+impl Inline {
+    /// The human readable form of the element, as it should appear in a summary.
+    pub fn text(&self) -> &str {
+        match self {
+            Inline::Text(text) => text,
+            Inline::Link { label, target } => {
+                if label.is_empty() {
+                    target
+                } else {
+                    label
+                }
+            }
+        }
+    }
+}
+
+/// Splits a description into text and `[label](target)` links. Brackets that do not form a
+/// complete link are kept as text.
+pub fn parse_inline(s: &str) -> Vec<Inline> {
+    let mut elements = Vec::new();
+    let mut text = String::new();
+
+    let mut rest = s;
+    while let Some(open) = rest.find('[') {
+        text.push_str(&rest[..open]);
+        let candidate = &rest[open..];
+
+        match parse_link(candidate) {
+            Some((link, consumed)) => {
+                if !text.is_empty() {
+                    elements.push(Inline::Text(std::mem::take(&mut text)));
+                }
+                trace!("parsed link: {:?}", link);
+                elements.push(link);
+                rest = &candidate[consumed..];
+            }
+            None => {
+                text.push('[');
+                rest = &candidate[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        elements.push(Inline::Text(text));
+    }
+
+    elements
+}
+
+/// Parses a link from the start of `s`, returning the link and the number of bytes it spans.
+fn parse_link(s: &str) -> Option<(Inline, usize)> {
+    let label_end = s.find(']')?;
+    let label = &s[1..label_end];
+    // a nested bracket means this was not the start of a link
+    if label.contains('[') {
+        return None;
+    }
+
+    let target_start = label_end + 1;
+    if !s[target_start..].starts_with('(') {
+        return None;
+    }
+
+    // find the matching closing parenthesis, targets may contain parentheses of their own
+    let mut depth = 0;
+    for (idx, c) in s[target_start..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    let target_end = target_start + idx;
+                    let link = Inline::Link {
+                        label: label.trim().to_owned(),
+                        target: s[target_start + 1..target_end].trim().to_owned(),
+                    };
+                    return Some((link, target_end + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+pub fn is_url(s: &str) -> bool {
+    ["http://", "https://", "mailto:", "www."]
+        .iter()
+        .any(|prefix| s.starts_with(prefix))
+}
+
+/// Renders the elements into a readable one-line summary with the link syntax removed.
+pub fn render_summary(elements: &[Inline]) -> String {
+    elements.iter().map(Inline::text).collect()
+}
+
+/// Returns the first link target that is an URL.
+pub fn first_url(elements: &[Inline]) -> Option<&str> {
+    elements.iter().find_map(|element| match element {
+        Inline::Link { target, .. } if is_url(target) => Some(target.as_str()),
+        _ => None,
+    })
+}
+
+/// Renders the targets of the non-URL links, which are not visible in the summary, one per
+/// line as "label: target".
+pub fn render_notes(elements: &[Inline]) -> String {
+    elements
+        .iter()
+        .filter_map(|element| match element {
+            // a link without a label already shows its target in the summary
+            Inline::Link { label, target } if !label.is_empty() && !is_url(target) => {
+                Some(format!("{}: {}", label, target))
+            }
+            _ => None,
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
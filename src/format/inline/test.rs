@@ -0,0 +1,55 @@
+use super::*;
+
+fn link(label: &str, target: &str) -> Inline {
+    Inline::Link {
+        label: label.to_owned(),
+        target: target.to_owned(),
+    }
+}
+
+#[test]
+fn links_parse_correct() {
+    assert_eq!(
+        parse_inline("[ICPW DL](2.4 Final advance of TAU students' project)"),
+        vec![link(
+            "ICPW DL",
+            "2.4 Final advance of TAU students' project"
+        )]
+    );
+    assert_eq!(
+        parse_inline("[](PRML kirjan laina-aika päättyy)"),
+        vec![link("", "PRML kirjan laina-aika päättyy")]
+    );
+    assert_eq!(
+        parse_inline("tilit (budjetti): [vuokra](302.33), oma tili"),
+        vec![
+            Inline::Text("tilit (budjetti): ".to_owned()),
+            link("vuokra", "302.33"),
+            Inline::Text(", oma tili".to_owned()),
+        ]
+    );
+    assert_eq!(
+        parse_inline("[docs](https://example.com/a_(b))"),
+        vec![link("docs", "https://example.com/a_(b)")]
+    );
+}
+
+#[test]
+fn incomplete_links_are_text() {
+    for s in &["[08:30][]", "buy [milk", "[label] (target)", "[a](b"] {
+        assert_eq!(parse_inline(s), vec![Inline::Text(s.to_string())]);
+    }
+}
+
+#[test]
+fn links_render_correct() {
+    let elements = parse_inline("[ICPW DL](3 Final reflections), see [site](https://example.com)");
+    assert_eq!(render_summary(&elements), "ICPW DL, see site");
+    assert_eq!(first_url(&elements), Some("https://example.com"));
+    assert_eq!(render_notes(&elements), "ICPW DL: 3 Final reflections");
+
+    let elements = parse_inline("[](PRML kirjan laina-aika päättyy)");
+    assert_eq!(render_summary(&elements), "PRML kirjan laina-aika päättyy");
+    assert_eq!(first_url(&elements), None);
+    assert_eq!(render_notes(&elements), "");
+}
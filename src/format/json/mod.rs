@@ -0,0 +1,17 @@
+use super::registry::Writer;
+use super::Event;
+use std::io;
+
+/// Writes the events as a pretty-printed JSON array.
+pub struct JsonWriter;
+
+impl Writer for JsonWriter {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn write(&self, events: &[Event], out: &mut dyn io::Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, events)?;
+        writeln!(out)
+    }
+}
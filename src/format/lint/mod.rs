@@ -0,0 +1,358 @@
+#[cfg(test)]
+mod test;
+
+use super::alpha::{parse_weekday, Tag};
+use super::document::{self, Item};
+use super::{parse_date, parse_date_parts, parse_time_ranges, DateVariant, Event, Span};
+use chrono::prelude::*;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// The problems found by `check_alpha`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Lint {
+    /// An event is dated before the one preceding it in the same year block or section.
+    OutOfOrder,
+    /// The weekday label does not match the date, eg. `ma 25.04.2019` is a Thursday.
+    WeekdayMismatch,
+    /// The same date and description appear twice.
+    Duplicate,
+    /// A span ends before it starts, eg. `10.05-08.05` or `12:00-11:00`.
+    BackwardsSpan,
+    /// An event in the past is still tagged to be published.
+    PastPublished,
+    /// The tag column has characters that are not in the legend, eg. `pZ` or `p!2x`.
+    UnknownTag,
+    /// The description starts with what may be a tag column with unknown tags or an acronym,
+    /// eg. `HR` or `oOo`.
+    MaybeUnknownTag,
+    /// A line starts with what looks like a date but is not one, eg. `31.02`.
+    InvalidDate,
+}
+
+impl Lint {
+    pub fn severity(self) -> Severity {
+        match self {
+            Lint::PastPublished | Lint::MaybeUnknownTag => Severity::Warning,
+            Lint::OutOfOrder
+            | Lint::WeekdayMismatch
+            | Lint::Duplicate
+            | Lint::BackwardsSpan
+            | Lint::UnknownTag
+            | Lint::InvalidDate => Severity::Error,
+        }
+    }
+}
+
+/// A problem on a line of the source. Line numbers are zero-based.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub lint: Lint,
+    pub line: usize,
+    /// The text the problem is in, when it is known.
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.lint.severity()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// Formats the diagnostic with a one-based line number and column, eg. `3:5: warning: ...`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity() {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}", self.line + 1)?;
+        if let Some(span) = &self.span {
+            write!(f, ":{}", span.columns.start + 1)?;
+        }
+        write!(f, ": {}: {}", severity, self.message)
+    }
+}
+
+/// Checks an alpha file for ordering and consistency problems. Events ending before `today` are
+/// in the past.
+pub fn check_alpha(start_year: i32, source: &str, today: NaiveDate) -> Vec<Diagnostic> {
+    let document = document::parse_alpha(start_year, source);
+    let lines = source.split('\n').collect::<Vec<&str>>();
+    let mut diagnostics = Vec::new();
+
+    let mut previous: Option<&Event> = None;
+    let mut seen: Vec<&Event> = Vec::new();
+    for item in document.items() {
+        let event = match item {
+            Item::Event { event, .. } => event,
+            Item::Unparsed { line, .. } => {
+                let text = lines[*line];
+                if let Some((lint, token, message)) = invalid_date(text, document.year_at(*line)) {
+                    diagnostics.push(Diagnostic {
+                        lint,
+                        line: *line,
+                        span: Some(Span::of(text, token).relocate(*line, offset(source, text))),
+                        message,
+                    });
+                }
+                continue;
+            }
+            // the order starts over in every year block and section
+            Item::Year { .. } | Item::Section { .. } => {
+                previous = None;
+                continue;
+            }
+        };
+        let line = event.source.lines.start;
+        let date_span = event.source.date.as_ref();
+        let mut report = |lint, span: Option<&Span>, message| {
+            diagnostics.push(Diagnostic {
+                lint,
+                line,
+                span: span.cloned(),
+                message,
+            })
+        };
+
+        // imprecise and repeating dates are not in any particular order
+        let ordered = event.recurrence.is_none()
+            && !matches!(event.date, DateVariant::Month { .. } | DateVariant::Year(_));
+        if ordered {
+            if let Some(previous) = previous {
+                if event.date.start_date() < previous.date.start_date() {
+                    report(
+                        Lint::OutOfOrder,
+                        date_span,
+                        format!(
+                            "{} is before {} on line {}",
+                            event.date.start_date(),
+                            previous.date.start_date(),
+                            previous.source.lines.start + 1
+                        ),
+                    );
+                }
+            }
+            previous = Some(event);
+        }
+
+        if let Some(weekday) = event.weekday {
+            let date = event.date.start_date();
+            if weekday != date.weekday() {
+                report(
+                    Lint::WeekdayMismatch,
+                    date_span,
+                    format!("{} is a {}, not {}", date, date.weekday(), weekday),
+                );
+            }
+        }
+
+        let duplicate = seen
+            .iter()
+            .find(|other| other.date == event.date && other.description == event.description);
+        if let Some(other) = duplicate {
+            report(
+                Lint::Duplicate,
+                Some(&event.source.span),
+                format!("same as line {}", other.source.lines.start + 1),
+            );
+        }
+        seen.push(event);
+
+        if let Some(message) = backwards_span(lines[line]) {
+            report(Lint::BackwardsSpan, date_span, message);
+        }
+
+        if event.tags.contains(&Tag::PublishToIcs)
+            && event.recurrence.is_none()
+            && event.date.end_date() < today
+        {
+            let tag = event.tags.iter().position(|tag| *tag == Tag::PublishToIcs);
+            report(
+                Lint::PastPublished,
+                tag.and_then(|tag| event.source.tags.get(tag)),
+                format!(
+                    "{} is in the past but still tagged p",
+                    event.date.end_date()
+                ),
+            );
+        }
+
+        if let Some((token, certain)) = unknown_tags(event) {
+            let text = lines[line];
+            let lint = if certain {
+                Lint::UnknownTag
+            } else {
+                Lint::MaybeUnknownTag
+            };
+            // the token is in the description, after the date
+            let after = date_span.map_or(0, |span| span.bytes.end - offset(source, text));
+            let span = text[after..]
+                .split_whitespace()
+                .find(|part| *part == token)
+                .map(|part| Span::of(text, part).relocate(line, offset(source, text)));
+            report(
+                lint,
+                span.as_ref(),
+                format!("\"{}\" looks like a tag column with unknown tags", token),
+            );
+        }
+    }
+
+    diagnostics
+}
+
+/// Checks the day headers of a calendar file for dates that do not parse, eg. `### Pe 31.2.`.
+pub fn check_calendar(year: i32, source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for (number, line) in source.split('\n').enumerate() {
+        if !line.starts_with("###") {
+            continue;
+        }
+        let invalid = line
+            .split_whitespace()
+            .skip(1)
+            .find(|token| date_like(token) && parse_date(token, year).is_none());
+        if let Some(token) = invalid {
+            diagnostics.push(Diagnostic {
+                lint: Lint::InvalidDate,
+                line: number,
+                span: Some(Span::of(line, token).relocate(number, offset(source, line))),
+                message: format!("\"{}\" is not a valid date", token),
+            });
+        }
+    }
+    diagnostics
+}
+
+/// The byte offset of `line`, a slice of `source`.
+fn offset(source: &str, line: &str) -> usize {
+    line.as_ptr() as usize - source.as_ptr() as usize
+}
+
+/// Looks like a date or a date span, eg. `31.02` or `1.-3.5`.
+fn date_like(token: &str) -> bool {
+    let token = token.trim_start_matches('~');
+    token.contains('.')
+        && token.chars().any(|c| c.is_ascii_digit())
+        && token
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.' || c == '-' || c == 'x')
+}
+
+/// Finds the date that does not parse at the start of a line that is not an event, or the times
+/// after it that do not fit its days, eg. `10-16,10-14` on a single day, or that end before they
+/// start, eg. `12:00-11:00`.
+fn invalid_date(line: &str, year: i32) -> Option<(Lint, &str, String)> {
+    let mut tokens = line
+        .split_whitespace()
+        .skip_while(|token| parse_weekday(token).is_some());
+    let token = tokens.next()?;
+    if !date_like(token) {
+        return None;
+    }
+    // the date is checked on its own, the line may be missing the description
+    if Event::from_str(&format!("{} x", token), year).is_err() {
+        return Some((
+            Lint::InvalidDate,
+            token,
+            format!("\"{}\" is not a valid date", token),
+        ));
+    }
+    let times = tokens.next()?;
+    let ranges = parse_time_ranges(times.trim_start_matches('~'))?;
+    if Event::from_str(&format!("{} {} x", token, times), year).is_ok() {
+        return None;
+    }
+    let backwards = ranges
+        .iter()
+        .any(|range| matches!((range.start, range.end), (Some(start), Some(end)) if end < start));
+    if backwards {
+        return Some((
+            Lint::BackwardsSpan,
+            times,
+            format!("{} ends before it starts", times),
+        ));
+    }
+    Some((
+        Lint::InvalidDate,
+        times,
+        format!(
+            "the times \"{}\" do not fit the days of \"{}\"",
+            times, token
+        ),
+    ))
+}
+
+/// Finds a date span that ends before it starts. A date span without years rolls over to the next
+/// year, so only the spans that cannot be over the turn of the year, from December to January,
+/// are taken to be backwards. The times that end before they start do not parse, see
+/// `invalid_date`.
+fn backwards_span(line: &str) -> Option<String> {
+    let token = line
+        .split_whitespace()
+        .find(|token| parse_weekday(token).is_none())?
+        .trim_start_matches('~');
+    let (left, right) = token.split_once('-')?;
+    let (start_day, start_month, start_year) = parse_date_parts(left)?;
+    let (end_day, end_month, end_year) = parse_date_parts(right)?;
+    if start_year.is_some() || end_year.is_some() {
+        return None;
+    }
+    let over_new_year = start_month == 12 && end_month == 1;
+    if (end_month, end_day) < (start_month, start_day) && !over_new_year {
+        return Some(format!("{} ends before it starts", token));
+    }
+    None
+}
+
+/// Finds a description starting with what looks like a tag column that did not parse, eg. `pZ`:
+/// a short token starting with a tag key and having no more other capital letters, digits or `!`
+/// than tag keys. The token is certainly a tag column when the tag keys are followed by reminders
+/// that did not parse, eg. `p!2x`, or by a single unknown character that does not make the token
+/// look like an acronym, eg. `pZ` but not `HR` or `T1`. Acronyms such as `PRML` or `TAU` are not
+/// taken for tags at all.
+fn unknown_tags(event: &Event) -> Option<(&str, bool)> {
+    if !event.tags.is_empty() || !event.reminders.is_empty() {
+        return None;
+    }
+    let token = event.description.split_whitespace().next()?;
+    let is_key = |c: char| c.to_string().parse::<Tag>().is_ok();
+
+    // the tag keys followed by reminders that did not parse
+    let rest = token.trim_start_matches(is_key);
+    if rest.len() < token.len() && rest.starts_with('!') {
+        return Some((token, true));
+    }
+
+    let keys = token.chars().filter(|c| is_key(*c)).count();
+    let unknown = token
+        .chars()
+        .filter(|c| !is_key(*c) && *c != '-')
+        .collect::<Vec<char>>();
+    let suspicious = unknown
+        .iter()
+        .all(|c| c.is_ascii_uppercase() || *c == '!' || c.is_ascii_digit());
+    let has_reminder = token.contains('!');
+    let starts_with_key = token.chars().next().is_some_and(is_key);
+    if !(starts_with_key
+        && !unknown.is_empty()
+        && keys >= unknown.len()
+        && suspicious
+        && (token.len() <= 4 || has_reminder))
+    {
+        return None;
+    }
+
+    let acronym = token
+        .chars()
+        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+    let certain = rest.chars().count() == 1 && !acronym;
+    Some((token, certain))
+}
@@ -0,0 +1,131 @@
+use super::{check_alpha, Lint, Severity};
+use chrono::NaiveDate;
+
+fn lints(source: &str) -> Vec<(usize, Lint)> {
+    let today = NaiveDate::from_ymd_opt(2019, 6, 1).unwrap();
+    check_alpha(2019, source, today)
+        .into_iter()
+        .map(|diagnostic| (diagnostic.line, diagnostic.lint))
+        .collect()
+}
+
+#[test]
+fn clean_file_has_no_diagnostics() {
+    let source = "\
+    01.06   p   kesä alkaa
+to  25.07       Saskia's Music Festival
+    30.12-2.1   uusi vuosi
+    xx.07       Rytmiitti
+
+### Repeating
+    04.xx       tilit
+";
+    assert_eq!(lints(source), vec![]);
+}
+
+#[test]
+fn problems_are_reported() {
+    let source = "\
+    04.09       tilit
+    03.09       vuokra
+ma  25.04       PRML
+    10.05-08.05 kesäloma
+    12.06 12:00-11:00 lounas
+    12.06 12:00-13:00 lounas
+    12.06 12:00-13:00 lounas
+    01.05   p   vappu
+    20.06   pZ  juhannus
+";
+    assert_eq!(
+        lints(source),
+        vec![
+            (1, Lint::OutOfOrder),
+            (2, Lint::OutOfOrder),
+            (2, Lint::WeekdayMismatch),
+            (3, Lint::BackwardsSpan),
+            (4, Lint::BackwardsSpan),
+            (6, Lint::Duplicate),
+            (7, Lint::OutOfOrder),
+            (7, Lint::PastPublished),
+            (8, Lint::UnknownTag),
+        ]
+    );
+    assert_eq!(Lint::UnknownTag.severity(), Severity::Error);
+}
+
+#[test]
+fn diagnostics_point_at_the_date() {
+    let today = NaiveDate::from_ymd_opt(2019, 6, 1).unwrap();
+    let diagnostics = check_alpha(
+        2019,
+        "    04.09       tilit\nma  03.09       vuokra\n",
+        today,
+    );
+
+    let columns = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.span.as_ref().unwrap().columns.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(columns, vec![4..9, 4..9]);
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "2:5: error: 2019-09-03 is before 2019-09-04 on line 1"
+    );
+}
+
+#[test]
+fn invalid_dates_are_reported() {
+    let source = "    31.02       karkauspäivä\n    xx.13   p   jotain\n    1.3.-20 tentti\n    20.05   9-11,13-15   kurssi\n";
+    assert_eq!(
+        lints(source),
+        vec![
+            (0, Lint::InvalidDate),
+            (1, Lint::InvalidDate),
+            (3, Lint::InvalidDate)
+        ]
+    );
+
+    let source = "### Pe 30.8.\n* 12:00 lounas\n### La 32.8.\n";
+    let diagnostics = super::check_calendar(2019, source);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].line, 2);
+    assert_eq!(diagnostics[0].span.as_ref().unwrap().columns, 7..12);
+}
+
+#[test]
+fn acronyms_are_only_warned_about() {
+    let source = "\
+    02.09       TTY lukuvuosi-ilmo
+    03.09       HR palaveri
+    04.09       oOo juhlat
+    05.09       T1 tentti
+    06.09       pZ juhlat
+    07.09       p!2x muistutus
+";
+    assert_eq!(
+        lints(source),
+        vec![
+            (0, Lint::MaybeUnknownTag),
+            (1, Lint::MaybeUnknownTag),
+            (2, Lint::MaybeUnknownTag),
+            (3, Lint::MaybeUnknownTag),
+            (4, Lint::UnknownTag),
+            (5, Lint::UnknownTag),
+        ]
+    );
+    assert_eq!(Lint::MaybeUnknownTag.severity(), Severity::Warning);
+
+    let today = NaiveDate::from_ymd_opt(2019, 6, 1).unwrap();
+    let diagnostics = check_alpha(2019, source, today);
+    assert_eq!(diagnostics[4].span.as_ref().unwrap().columns, 16..18);
+    assert_eq!(
+        diagnostics[4].to_string(),
+        "5:17: error: \"pZ\" looks like a tag column with unknown tags"
+    );
+}
+
+#[test]
+fn unparsed_lines_do_not_restart_the_order() {
+    let source = "    04.09       tilit\njotain muuta\n    03.09       vuokra\n### Repeating\n    01.09       vuokra\n";
+    assert_eq!(lints(source), vec![(2, Lint::OutOfOrder)]);
+}
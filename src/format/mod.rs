@@ -1,14 +1,36 @@
+pub mod agenda;
 pub mod alpha;
 pub mod calendar;
+pub mod context;
+pub mod diff;
+pub mod document;
+pub mod event;
+pub mod filter;
+pub mod ical;
+pub mod inline;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod lint;
+pub mod reference;
+pub mod registry;
 
+use alpha::Tag;
 use chrono::prelude::*;
+use chrono::Duration;
 use chrono_tz::{Europe, Tz};
-use ics::properties::{Comment, DtEnd, DtStart, Summary};
-use uuid::Uuid;
 
+pub use context::{DateContext, YearPolicy};
+pub use event::{Event, Recurrence, Source, SourceFormat, Span};
+pub use filter::Filter;
+pub use ical::{CreateIcsEvent, CreateIcsTodo, IcsConfig, IcsWriter, TodoPolicy};
+#[cfg(feature = "serde")]
+pub use json::JsonWriter;
+pub use registry::{Parser, Registry, Writer};
+
+/// The time zone of the notes unless the context names another.
 const TZ: Tz = Europe::Helsinki;
-// HACK: use the timezone directly in the format
-const DATETIME_FORMAT: &str = "%Y%m%dT%H%M%S+02:00";
+// times are written in UTC, the clients show them in their own time zone
+const DATETIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
 const DATE_FORMAT: &str = "%Y%m%d";
 
 lazy_static! {
@@ -18,105 +40,261 @@ static ref TIME_FORMATS: Vec<&'static str> = vec![
 ];
 }
 
-pub trait Event {
-    fn date(&self) -> &DateVariant;
-    fn description(&self) -> &str;
-}
-
-pub trait CreateIcsEvent {
-    fn create_ics_event<'a>(&'a self) -> ics::Event<'a>;
-}
-
-/// Ordered from most specific and well specified to least specific / context dependent.
+/// Ordered from most specific and well specified to least specific / context dependent. The times
+/// keep the offset of the time zone of the notes they were read from.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Clone)]
 pub enum DateVariant {
-    TimeSpan(DateTime<Local>, DateTime<Local>),
-    DateTime(DateTime<Local>),
-    Date(Date<Local>),
+    TimeSpan(DateTime<FixedOffset>, DateTime<FixedOffset>),
+    DateTime(DateTime<FixedOffset>),
+    Date(NaiveDate),
     Month { year: u32, month: u32 },
     Year(u32),
 }
 
-impl<T> CreateIcsEvent for T
-where
-    T: Event,
-{
-    fn create_ics_event<'a>(&'a self) -> ics::Event<'a> {
-        let mut event = ics::Event::new(
-            Uuid::new_v4().to_string(),
-            Utc::now().format(DATETIME_FORMAT).to_string(),
-        );
-        match self.date() {
+impl std::fmt::Display for DateVariant {
+    /// Formats the date in the time zone of the notes, eg. `Fri 2019-08-30 12:00-13:00`.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
             DateVariant::TimeSpan(start, end) => {
-                event.push(DtStart::new(start.format(DATETIME_FORMAT).to_string()));
-                event.push(DtEnd::new(end.format(DATETIME_FORMAT).to_string()));
-            }
-            DateVariant::DateTime(date) => {
-                event.push(DtStart::new(date.format(DATETIME_FORMAT).to_string()));
+                if start.date_naive() == end.date_naive() {
+                    write!(f, "{}-{}", start.format("%a %F %H:%M"), end.format("%H:%M"))
+                } else {
+                    write!(
+                        f,
+                        "{} - {}",
+                        start.format("%a %F %H:%M"),
+                        end.format("%a %F %H:%M")
+                    )
+                }
             }
-            DateVariant::Date(date) => {
-                let date_fmt = date.format(DATE_FORMAT);
-                let date_str = date_fmt.to_string();
-                event.push(DtStart::new(date_str));
-            }
-            DateVariant::Month { .. } => {
-                unimplemented!("converting 'year/month' events into .ics is not implemented");
+            DateVariant::DateTime(date) => write!(f, "{}", date.format("%a %F %H:%M")),
+            DateVariant::Date(date) => write!(f, "{}", date.format("%a %F")),
+            DateVariant::Month { year, month } => write!(f, "{}-{:02}", year, month),
+            DateVariant::Year(year) => write!(f, "{}", year),
+        }
+    }
+}
+
+impl DateVariant {
+    /// The first day covered by the date.
+    pub fn start_date(&self) -> NaiveDate {
+        match self {
+            DateVariant::TimeSpan(start, _) => start.date_naive(),
+            DateVariant::DateTime(date) => date.date_naive(),
+            DateVariant::Date(date) => *date,
+            DateVariant::Month { year, month } => {
+                NaiveDate::from_ymd_opt(*year as i32, *month, 1).unwrap()
             }
-            DateVariant::Year(_) => {
-                unimplemented!("converting 'year' events into .ics is not implemented");
+            DateVariant::Year(year) => NaiveDate::from_ymd_opt(*year as i32, 1, 1).unwrap(),
+        }
+    }
+
+    /// The last day covered by the date.
+    pub fn end_date(&self) -> NaiveDate {
+        match self {
+            DateVariant::TimeSpan(_, end) => end.date_naive(),
+            DateVariant::Month { year, month } => {
+                let (year, month) = if *month == 12 {
+                    (*year as i32 + 1, 1)
+                } else {
+                    (*year as i32, month + 1)
+                };
+                NaiveDate::from_ymd_opt(year, month, 1)
+                    .unwrap()
+                    .pred_opt()
+                    .unwrap()
             }
+            DateVariant::Year(year) => NaiveDate::from_ymd_opt(*year as i32, 12, 31).unwrap(),
+            _ => self.start_date(),
         }
-        event.push(Summary::new(self.description()));
-        event.push(Comment::new("created with memoparsa"));
-        event
     }
 }
 
+/// Parses an offset such as `30m`, `1h`, `2d` or `1w`.
+pub fn parse_offset(s: &str) -> Option<Duration> {
+    let unit = s.chars().last()?;
+    let amount = s[..s.len() - unit.len_utf8()].parse::<i64>().ok()?;
+    match unit {
+        'm' => Some(Duration::minutes(amount)),
+        'h' => Some(Duration::hours(amount)),
+        'd' => Some(Duration::days(amount)),
+        'w' => Some(Duration::weeks(amount)),
+        _ => None,
+    }
+}
+
+/// Parses a date, taking the year from the token if it has one and from `year` otherwise.
 pub fn parse_date(s: &str, year: i32) -> Option<NaiveDate> {
     trace!("attempting to parse date from: {}", s);
 
-    let parts = s.split('.').collect::<Vec<&str>>();
-    // cannot contain both a day and a month
-    if parts.len() < 2 {
-        return None;
-    }
-
-    let day = match parts[0].parse::<u32>() {
-        Ok(u) => u,
-        Err(_) => return None,
-    };
-    let month = match parts[1].parse::<u32>() {
-        Ok(u) => u,
-        Err(_) => return None,
-    };
+    let (day, month, explicit_year) = parse_date_parts(s)?;
+    let year = explicit_year.unwrap_or(year);
 
     let date = match NaiveDate::from_ymd_opt(year, month, day) {
         Some(date) => date,
-        None => panic!("invalid date: {}-{}-{}", year, month, day),
+        None => {
+            warn!("invalid date: {}-{}-{}", year, month, day);
+            return None;
+        }
     };
     trace!("parsed: {:?}", date);
     Some(date)
 }
 
-pub fn parse_timespan(s: &str) -> Option<(NaiveTime, NaiveTime)> {
-    let timespan_parts = s.split('-').collect::<Vec<&str>>();
-    if timespan_parts.len() != 2 {
+/// Parses the day, the month and the optional year of a date token: `d.m`, `d.m.`, `d.m.yyyy`,
+/// `d.m.yy` or `d.m.-yy`. Two-digit years are of the 2000s.
+pub fn parse_date_parts(s: &str) -> Option<(u32, u32, Option<i32>)> {
+    let parts = s.split('.').collect::<Vec<&str>>();
+    // must contain both a day and a month, and at most a year
+    if parts.len() < 2 || parts.len() > 3 {
         return None;
     }
-    let left = timespan_parts[0];
-    let right = timespan_parts[1];
 
-    let end_time = match parse_time(right) {
-        Some(time) => time,
-        None => return None,
+    let day = parts[0].parse::<u32>().ok()?;
+    let month = parts[1].parse::<u32>().ok()?;
+    let year = match parts.get(2) {
+        None | Some(&"") => None,
+        Some(year) => Some(parse_year(year.trim_start_matches('-'))?),
     };
 
-    let start_time = match parse_time(left) {
-        Some(time) => time,
-        None => return None,
+    Some((day, month, year))
+}
+
+fn parse_year(s: &str) -> Option<i32> {
+    if !s.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    match s.len() {
+        2 => Some(2000 + s.parse::<i32>().ok()?),
+        4 => s.parse::<i32>().ok(),
+        _ => None,
+    }
+}
+
+/// A time range where either end may be left open, eg. `11-` or `-19`. A lone time such as
+/// `12:00` is a range with only a start.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TimeRange {
+    pub start: Option<NaiveTime>,
+    pub end: Option<NaiveTime>,
+}
+
+/// Parses a comma separated list of times and time ranges, one for each day of a date span, eg.
+/// `11-,-19` for "from 11 on the first day until 19 on the last day".
+pub fn parse_time_ranges(s: &str) -> Option<Vec<TimeRange>> {
+    s.split(',').map(parse_time_range).collect()
+}
+
+/// Parses a time (`12:00`), a time span (`12:40-13:20`, `9-11`) or an open time span (`11-`,
+/// `-19`). Bare hours are only accepted as a part of a span.
+pub fn parse_time_range(s: &str) -> Option<TimeRange> {
+    let timespan_parts = s.split('-').collect::<Vec<&str>>();
+    match timespan_parts.as_slice() {
+        [time] => Some(TimeRange {
+            start: Some(parse_time(time)?),
+            end: None,
+        }),
+        [left, right] => {
+            let start = match *left {
+                "" => None,
+                left => Some(parse_hour_or_time(left)?),
+            };
+            let end = match *right {
+                "" => None,
+                right => Some(parse_hour_or_time(right)?),
+            };
+            // a lone dash is not a time
+            if start.is_none() && end.is_none() {
+                return None;
+            }
+            Some(TimeRange { start, end })
+        }
+        _ => None,
+    }
+}
+
+fn parse_hour_or_time(s: &str) -> Option<NaiveTime> {
+    parse_time(s).or_else(|| NaiveTime::from_hms_opt(s.parse::<u32>().ok()?, 0, 0))
+}
+
+/// Resolves times parsed by `parse_time_ranges` onto the days from `start_date` to `end_date` in
+/// the time zone. A single range spans from its start on the first day to its end on the last. A
+/// list has a range for each day, eg. `10-16,10-14` on two days, and spans from the start of the
+/// first range on the first day to the end of the last range on the last day. A list that does not
+/// have a range for each day, or a range that ends before it starts, resolves to nothing.
+pub fn resolve_times(
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    ranges: &[TimeRange],
+    timezone: Tz,
+) -> Option<DateVariant> {
+    let days = (end_date - start_date).num_days() + 1;
+    if ranges.len() > 1 && ranges.len() as i64 != days {
+        warn!(
+            "the {} times {:?} do not fit the {} days of {} - {}",
+            ranges.len(),
+            ranges,
+            days,
+            start_date,
+            end_date
+        );
+        return None;
+    }
+    // the ranges of a list are each on their own day
+    let backwards = ranges.len() > 1
+        && ranges
+            .iter()
+            .any(|range| matches!(range, TimeRange { start: Some(start), end: Some(end) } if end < start));
+    let start_time = ranges.first().and_then(|range| range.start);
+    let end_time = ranges.last().and_then(|range| range.end);
+
+    // HACK: 06:00 and 23:59 seem like sensible defaults for the open ends of a span
+    let default_start = NaiveTime::from_hms_opt(6, 0, 0).unwrap();
+    let default_end = NaiveTime::from_hms_opt(23, 59, 0).unwrap();
+
+    let date = match (start_time, end_time) {
+        (None, None) if start_date == end_date => DateVariant::Date(start_date),
+        (Some(start_time), None) if start_date == end_date => {
+            DateVariant::DateTime(local(start_date.and_time(start_time), timezone))
+        }
+        (start_time, end_time) => DateVariant::TimeSpan(
+            local(
+                start_date.and_time(start_time.unwrap_or(default_start)),
+                timezone,
+            ),
+            local(end_date.and_time(end_time.unwrap_or(default_end)), timezone),
+        ),
     };
+    if let DateVariant::TimeSpan(start, end) = &date {
+        if backwards || end < start {
+            warn!(
+                "the times {:?} on {} - {} end before they start",
+                ranges, start_date, end_date
+            );
+            return None;
+        }
+    }
+    Some(date)
+}
 
-    Some((start_time, end_time))
+/// Interprets a wall-clock time in the time zone of the notes. A time skipped by a change to
+/// daylight saving time is moved forward by the change, eg. 03:30 to 04:30.
+fn local(date_time: NaiveDateTime, timezone: Tz) -> DateTime<FixedOffset> {
+    timezone
+        .from_local_datetime(&date_time)
+        .earliest()
+        .or_else(|| {
+            warn!(
+                "{} is skipped in {}, moving it an hour later",
+                date_time, timezone
+            );
+            timezone
+                .from_local_datetime(&(date_time + Duration::hours(1)))
+                .earliest()
+        })
+        .unwrap()
+        .fixed_offset()
 }
 
 pub fn parse_time(s: &str) -> Option<NaiveTime> {
@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod test;
+
+use super::parse_date_parts;
+use chrono::prelude::*;
+
+/// A date mentioned in the text of an event, eg. the deadline in `(DL: 1.3.2020)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DateReference {
+    /// What the date is about, eg. "DL".
+    pub label: String,
+    pub date: NaiveDate,
+}
+
+/// Finds the dates referenced in `text`, either inside parentheses as in `(DL: 1.3.2020)` and
+/// `(aikaisintaan 1.7.-19)`, or leading the text as in `2.9.: ilmo päättyy`. Dates without a year
+/// are taken to be the first such date on or after `after`.
+pub fn extract_references(text: &str, after: NaiveDate) -> Vec<DateReference> {
+    let mut references = Vec::new();
+
+    // leading date, the rest of the text tells what it is about
+    let text = text.trim();
+    if let Some((first, rest)) = text.split_once(char::is_whitespace) {
+        if let Some(token) = first.strip_suffix(':') {
+            if let Some(date) = resolve_date(token, after) {
+                references.push(DateReference {
+                    label: rest.trim().to_owned(),
+                    date,
+                });
+            }
+        }
+    }
+
+    // dates at the end of parenthesized remarks, the rest of the remark tells what it is about
+    let mut rest = text;
+    while let Some(open) = rest.find('(') {
+        let remark = &rest[open + 1..];
+        let close = match remark.find(')') {
+            Some(close) => close,
+            None => break,
+        };
+        rest = &remark[close + 1..];
+        let remark = &remark[..close];
+
+        let (label, token) = match remark.trim().rsplit_once(char::is_whitespace) {
+            Some((label, token)) => (label, token),
+            None => ("", remark.trim()),
+        };
+        if let Some(date) = resolve_date(token, after) {
+            trace!("found date reference {:?} in \"{}\"", date, text);
+            references.push(DateReference {
+                label: label.trim().trim_end_matches(':').to_owned(),
+                date,
+            });
+        }
+    }
+
+    references
+}
+
+fn resolve_date(token: &str, after: NaiveDate) -> Option<NaiveDate> {
+    let (day, month, year) = parse_date_parts(token)?;
+    match year {
+        Some(year) => NaiveDate::from_ymd_opt(year, month, day),
+        None => {
+            let date = NaiveDate::from_ymd_opt(after.year(), month, day)?;
+            if date < after {
+                NaiveDate::from_ymd_opt(after.year() + 1, month, day)
+            } else {
+                Some(date)
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use super::*;
+
+fn reference(label: &str, year: i32, month: u32, day: u32) -> DateReference {
+    DateReference {
+        label: label.to_owned(),
+        date: NaiveDate::from_ymd_opt(year, month, day).unwrap(),
+    }
+}
+
+#[test]
+fn references_extract_correct() {
+    let after = NaiveDate::from_ymd_opt(2019, 7, 1).unwrap();
+
+    assert_eq!(
+        extract_references(
+            "hanki suuhygienistiaika (käy puhumassa YTHS:llä) (8-12) (DL: 1.3.2020) (aikaisintaan 1.7.-19)",
+            after
+        ),
+        vec![
+            reference("DL", 2020, 3, 1),
+            reference("aikaisintaan", 2019, 7, 1)
+        ]
+    );
+    assert_eq!(
+        extract_references(
+            "Kevään '19 valmistumishakemuksien viimeinen jättöpäivä (valm. päiv: 19.6.)",
+            NaiveDate::from_ymd_opt(2019, 5, 29).unwrap()
+        ),
+        vec![reference("valm. päiv", 2019, 6, 19)]
+    );
+    assert_eq!(
+        extract_references(
+            "2.9.: TTY lukuvuosi-ilmo päättyy, ilmoan jos en valmis",
+            after
+        ),
+        vec![reference(
+            "TTY lukuvuosi-ilmo päättyy, ilmoan jos en valmis",
+            2019,
+            9,
+            2
+        )]
+    );
+}
+
+#[test]
+fn references_roll_into_next_year() {
+    let after = NaiveDate::from_ymd_opt(2019, 12, 1).unwrap();
+
+    assert_eq!(
+        extract_references("STICKY+: D-vitamiinikausi (until 28.3.)", after),
+        vec![reference("until", 2020, 3, 28)]
+    );
+}
+
+#[test]
+fn plain_text_has_no_references() {
+    let after = NaiveDate::from_ymd_opt(2019, 7, 1).unwrap();
+
+    for text in &[
+        "ICPW tunti (kts. POP)",
+        "tilit: (budjetti)",
+        "(19.6",
+        "klo 12.30",
+    ] {
+        assert_eq!(extract_references(text, after), vec![]);
+    }
+}
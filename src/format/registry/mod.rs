@@ -0,0 +1,145 @@
+#[cfg(test)]
+mod test;
+
+use super::alpha::writer::AlphaWriter;
+use super::document::{self, Document};
+use super::ical::IcsWriter;
+use super::{calendar, DateContext, Event, SourceFormat};
+use chrono::{Datelike, NaiveDate};
+use std::io;
+use std::path::Path;
+
+/// Parses a note format into a document.
+pub trait Parser {
+    /// The name of the format, eg. `alpha`, used to look the parser up from a `Registry`.
+    fn name(&self) -> &str;
+    /// Parses the source, with `year` as the year of dates without one.
+    fn parse(&self, year: i32, source: &str) -> Document;
+    /// Parses the source, inferring the years of dates without one from the context. Formats
+    /// that do not infer years read the dates in the year of the reference date.
+    fn parse_with_context(&self, context: &DateContext, source: &str) -> Document {
+        self.parse(context.reference.year(), source)
+    }
+}
+
+/// Writes events in an output format.
+pub trait Writer {
+    /// The name of the format, eg. `ics`, used to look the writer up from a `Registry`.
+    fn name(&self) -> &str;
+    fn write(&self, events: &[Event], out: &mut dyn io::Write) -> io::Result<()>;
+}
+
+impl<P> Parser for &P
+where
+    P: Parser + ?Sized,
+{
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+    fn parse(&self, year: i32, source: &str) -> Document {
+        (**self).parse(year, source)
+    }
+    fn parse_with_context(&self, context: &DateContext, source: &str) -> Document {
+        (**self).parse_with_context(context, source)
+    }
+}
+
+impl Parser for SourceFormat {
+    fn name(&self) -> &str {
+        SourceFormat::name(*self)
+    }
+
+    fn parse(&self, year: i32, source: &str) -> Document {
+        match self {
+            SourceFormat::Alpha => document::parse_alpha(year, source),
+            SourceFormat::Calendar => {
+                let date_ctx = NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+                Document::from_events(year, calendar::parse_calendar(source, date_ctx))
+            }
+        }
+    }
+
+    fn parse_with_context(&self, context: &DateContext, source: &str) -> Document {
+        match self {
+            SourceFormat::Alpha => document::parse_alpha_with_context(context, source),
+            SourceFormat::Calendar => Document::from_events(
+                context.reference.year(),
+                calendar::parse_calendar_with_context(source, context),
+            ),
+        }
+    }
+}
+
+/// The parsers and writers available by name. A parser or a writer replaces an earlier one with
+/// the same name.
+pub struct Registry {
+    parsers: Vec<Box<dyn Parser>>,
+    writers: Vec<Box<dyn Writer>>,
+}
+
+impl Default for Registry {
+    /// The built-in formats.
+    fn default() -> Self {
+        let mut registry = Registry::new();
+        registry.register_parser(Box::new(SourceFormat::Alpha));
+        registry.register_parser(Box::new(SourceFormat::Calendar));
+        registry.register_writer(Box::new(IcsWriter::default()));
+        registry.register_writer(Box::new(AlphaWriter));
+        #[cfg(feature = "serde")]
+        registry.register_writer(Box::new(super::json::JsonWriter));
+        registry
+    }
+}
+
+impl Registry {
+    /// A registry without any formats.
+    pub fn new() -> Self {
+        Registry {
+            parsers: vec![],
+            writers: vec![],
+        }
+    }
+
+    pub fn register_parser(&mut self, parser: Box<dyn Parser>) {
+        self.parsers.retain(|p| p.name() != parser.name());
+        self.parsers.push(parser);
+    }
+
+    pub fn register_writer(&mut self, writer: Box<dyn Writer>) {
+        self.writers.retain(|w| w.name() != writer.name());
+        self.writers.push(writer);
+    }
+
+    pub fn parser(&self, name: &str) -> Option<&dyn Parser> {
+        self.parsers
+            .iter()
+            .find(|parser| parser.name() == name)
+            .map(|parser| parser.as_ref())
+    }
+
+    /// The parser named in the name of the file, eg. `calendar` for `notes/CALENDAR.md`.
+    pub fn detect(&self, path: &str) -> Option<&dyn Parser> {
+        let file_name = Path::new(path).file_name()?.to_str()?.to_lowercase();
+        self.parsers
+            .iter()
+            .find(|parser| file_name.contains(&parser.name().to_lowercase()))
+            .map(|parser| parser.as_ref())
+    }
+
+    pub fn writer(&self, name: &str) -> Option<&dyn Writer> {
+        self.writers
+            .iter()
+            .find(|writer| writer.name() == name)
+            .map(|writer| writer.as_ref())
+    }
+
+    /// The names of the parsers in the order they were registered.
+    pub fn parser_names(&self) -> Vec<&str> {
+        self.parsers.iter().map(|parser| parser.name()).collect()
+    }
+
+    /// The names of the writers in the order they were registered.
+    pub fn writer_names(&self) -> Vec<&str> {
+        self.writers.iter().map(|writer| writer.name()).collect()
+    }
+}
@@ -0,0 +1,66 @@
+use super::{Parser, Registry, Writer};
+use crate::format::document::Document;
+use crate::format::Event;
+use std::io;
+
+struct Lines;
+
+impl Parser for Lines {
+    fn name(&self) -> &str {
+        "alpha"
+    }
+    fn parse(&self, year: i32, _: &str) -> Document {
+        Document {
+            start_year: year,
+            blocks: vec![],
+        }
+    }
+}
+
+struct Count;
+
+impl Writer for Count {
+    fn name(&self) -> &str {
+        "count"
+    }
+    fn write(&self, events: &[Event], out: &mut dyn io::Write) -> io::Result<()> {
+        write!(out, "{}", events.len())
+    }
+}
+
+#[test]
+fn registry_finds_formats_by_name() {
+    let mut registry = Registry::default();
+    assert!(registry.parser_names().starts_with(&["alpha", "calendar"]));
+    assert!(registry.writer_names().contains(&"ics"));
+
+    let source = "    01.05   p   vappu";
+    let document = registry.parser("alpha").unwrap().parse(2019, source);
+    assert_eq!(document.events().count(), 1);
+
+    // a parser replaces the one with the same name
+    registry.register_parser(Box::new(Lines));
+    registry.register_writer(Box::new(Count));
+    assert_eq!(registry.parser_names(), vec!["calendar", "alpha"]);
+    let document = registry.parser("alpha").unwrap().parse(2019, source);
+    assert_eq!(document.events().count(), 0);
+
+    let mut out = vec![];
+    let events = document.into_events();
+    registry
+        .writer("count")
+        .unwrap()
+        .write(&events, &mut out)
+        .unwrap();
+    assert_eq!(out, b"0");
+    assert!(registry.parser("markdown").is_none());
+}
+
+#[test]
+fn formats_are_detected_from_file_names() {
+    let registry = Registry::default();
+    let detect = |path| registry.detect(path).map(|parser| parser.name());
+    assert_eq!(detect("notes/CALENDAR.md"), Some("calendar"));
+    assert_eq!(detect("alpha2.md"), Some("alpha"));
+    assert_eq!(detect("calendar/notes.md"), None);
+}
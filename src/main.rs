@@ -1,88 +1,715 @@
 #[macro_use]
 extern crate clap;
 use chrono::prelude::*;
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::io::{IsTerminal, Read};
 
 fn main() {
     // enable full logging with RUST_LOG=memoparsa=trace
     env_logger::init();
 
+    // the formats are discovered from the registry, the names are copied out of it so that the
+    // writers can be configured after parsing the command line
+    let mut registry = memoparsa::Registry::default();
+    let parser_names = owned(registry.parser_names());
+    let writer_names = owned(registry.writer_names());
+    let parser_names = parser_names
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<&str>>();
+    let writer_names = writer_names
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<&str>>();
+    let default_year = Local::now().year().to_string();
+
     // handle command line arguments
-    let matches = cli();
+    let matches = cli(&parser_names, &writer_names, &default_year);
 
-    let source_contents = std::fs::read_to_string(matches.value_of("input").unwrap()).unwrap();
-    let format = match matches.value_of("format").unwrap().as_ref() {
-        "alpha" | "ALPHA" => memoparsa::SourceFormat::Alpha,
-        _ => panic!("unknown format"),
+    let (command, matches) = matches.subcommand();
+    let matches = matches.unwrap();
+    let code = match command {
+        "convert" => convert(matches, &mut registry, &parser_names),
+        "list" => list(matches, &registry, &parser_names),
+        "agenda" => agenda(matches, &registry, &parser_names),
+        "check" => check(matches, &registry, &parser_names),
+        "diff" => diff(matches, &registry, &parser_names),
+        "fmt" => fmt(matches, &registry),
+        _ => unreachable!(),
     };
-    let year = matches
-        .value_of("start-year")
-        .unwrap()
-        .parse::<i32>()
-        .unwrap();
-    let output_file = matches.value_of("output");
-
-    // do work
-    match output_file {
-        Some(output_file) => memoparsa::save_as_ics(format, year, &source_contents, output_file),
-        None => memoparsa::parse(format, year, &source_contents),
-    }
-
-    std::process::exit(exitcode::OK);
+    std::process::exit(code);
 }
 
-fn cli<'a>() -> clap::ArgMatches<'a> {
-    let matches = App::new("memoparsa")
+fn cli<'a>(
+    parser_names: &'a [&'a str],
+    writer_names: &'a [&'a str],
+    default_year: &'a str,
+) -> ArgMatches<'a> {
+    App::new("memoparsa")
         .version(crate_version!())
-        .author(crate_authors!("\n"))
+        .author(env!("CARGO_PKG_AUTHORS"))
         .about(crate_description!())
-        .arg(Arg::from_usage("-o, --output=[FILE] 'Sets custom output file'"))
-        .args_from_usage(
-            "<input>              'Sets input file to use'",
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::from_usage("-f, --format=[FORMAT] 'Sets the input format of the files that do not name one, detected from the file name by default, eg. alpha.md or CALENDAR.md'")
+                .possible_values(parser_names)
+                .case_insensitive(true)
+                .global(true),
+        )
+        .arg(
+            Arg::from_usage("--timezone=[TZ] 'Sets the time zone of the times in the files'")
+                .default_value("Europe/Helsinki")
+                .validator(|s| s.parse::<chrono_tz::Tz>().map(|_| ()).map_err(|e| e.to_string()))
+                .global(true),
+        )
+        .arg(
+            Arg::from_usage("-y, --start-year=[NUMBER] 'Sets custom start year as context for the input files. This year is used by default for formats that require it.'")
+                .default_value(default_year)
+                .global(true),
         )
         .arg(
-            Arg::from_usage("<format>             'Sets input format'")
-                .validator(validate_input_format_spec),
+            Arg::from_usage("--reference=[DATE] 'Sets the date the years of the dates without one are inferred from, instead of the start of the start year, eg. 2019-12-10 or 10.12.2019'")
+                .validator(|s| match parse_day(&s) {
+                    Some(_) => Ok(()),
+                    None => Err(format!("invalid date: {}", s)),
+                })
+                .global(true),
         )
         .arg(
-            Arg::from_usage("-y, --start-year=[NUMBER] 'Sets custom start year as context for the input file. This year is used by default for formats that require it.'")
-                .default_value_if("format", Some("alpha"), &Local::now().year().to_string())
+            Arg::from_usage("--year-policy=[POLICY] 'Sets how the years are inferred before the first year line: fixed in the year of the reference, window:N for the dates more than N months before the reference in the next year, or monotonic for the dates going backwards in the next year'")
+                .validator(|s| s.parse::<memoparsa::YearPolicy>().map(|_| ()).map_err(|e| e.to_string()))
+                .global(true),
+        )
+        .subcommand(
+            SubCommand::with_name("convert")
+                .about("Converts the events to a calendar or another output format")
+                .arg(input_arg())
+                .arg(Arg::from_usage("-o, --output=[FILE] 'Sets custom output file, - for stdout, or the output directory with --split-by'"))
+                .arg(
+                    Arg::from_usage("--split-by=[PARTITION] 'Writes one calendar per tag or section into the output directory'")
+                        .possible_values(&["tag", "section"])
+                        .requires("output"),
+                )
+                .arg(
+                    Arg::from_usage("--output-format=[FORMAT] 'Sets the output format, ics by default, the output is printed unless an output file is set'")
+                        .possible_values(writer_names)
+                        .conflicts_with("split-by"),
+                )
+                .arg(
+                    Arg::from_usage("--all 'Outputs every event instead of only the ones tagged p'")
+                        .conflicts_with("include-tag"),
+                )
+                .args(&filter_args())
+                .arg(Arg::from_usage("--approximate-prefix=[PREFIX] 'Sets the summary prefix for approximate events, \"[~] \" by default'"))
+                .arg(Arg::from_usage("--no-tentative 'Does not mark approximate events as tentative'"))
+                .arg(
+                    Arg::from_usage("--todo-tag=[TAG]... 'Writes entries with the tag as todos, H by default'")
+                        .number_of_values(1)
+                        .validator(|s| s.parse::<memoparsa::Tag>().map(|_| ()).map_err(|e| e.to_string())),
+                )
+                .arg(Arg::from_usage("--no-imprecise-todos 'Writes entries dated only by month or year as events instead of todos'"))
+                .arg(
+                    Arg::from_usage("--category=[FROM:TO]... 'Renames a category, eg. deadline:DL, or leaves it out when TO is empty'")
+                        .number_of_values(1)
+                        .validator(|s| match s.contains(':') {
+                            true => Ok(()),
+                            false => Err(format!("expected FROM:TO, got: {}", s)),
+                        }),
+                )
+                .arg(
+                    Arg::from_usage("--reminder=[TAG:OFFSET]... 'Sets a reminder for events with the tag, eg. r:2d or H:1w'")
+                        .number_of_values(1)
+                        .validator(|s| parse_tag_reminder(&s).map(|_| ())),
+                )
+                .arg(Arg::from_usage("--extract-references 'Creates linked events for dates mentioned in event descriptions, eg. (DL: 1.3.2020)'")),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("Lists the events, one per line")
+                .arg(input_arg())
+                .args(&filter_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("agenda")
+                .about("Shows the events of the upcoming days, day by day")
+                .arg(input_arg())
+                .args(&filter_args())
+                .arg(
+                    Arg::from_usage("--days=[N] 'Sets the number of days to show'")
+                        .default_value("7")
+                        .validator(|s| s.parse::<u32>().map(|_| ()).map_err(|e| e.to_string())),
+                )
+                .arg(
+                    Arg::from_usage("--from=[DATE] 'Sets the first day to show, today by default, eg. 2019-05-01 or 1.5.2019'")
+                        .validator(|s| match parse_day(&s) {
+                            Some(_) => Ok(()),
+                            None => Err(format!("invalid date: {}", s)),
+                        }),
+                ),
         )
-        .get_matches();
-    println!("Tester program for cli implementation");
-    if let Some(year) = matches.value_of("start-year") {
-        println!("Selected start-year: {}", year);
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Lints alpha and calendar files for ordering and consistency problems, failing on errors")
+                .arg(input_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Shows the events added, removed or changed between two versions of the events, failing if there are any")
+                .arg(Arg::from_usage("<old> 'Sets the older version, - for stdin, as FILE[:FORMAT[:YEAR]], after -- when it starts with -'"))
+                .arg(Arg::from_usage("<new> 'Sets the newer version, - for stdin, as FILE[:FORMAT[:YEAR]], after -- when it starts with -'")),
+        )
+        .subcommand(
+            SubCommand::with_name("fmt")
+                .about("Rewrites alpha files in place in the canonical layout")
+                .arg(Arg::from_usage("<input>... 'Sets the files to format, - to format stdin to stdout'"))
+                .arg(Arg::from_usage("--check 'Lists the files that are not formatted instead of rewriting them'")),
+        )
+        .get_matches()
+}
+
+fn input_arg<'a>() -> Arg<'a, 'a> {
+    Arg::from_usage("<input>... 'Sets the input files, - for stdin, each as FILE[:FORMAT[:YEAR]] to read it in its own format and start year. Stdin with a format or a year goes after --, eg. -- -:calendar'")
+}
+
+fn filter_args<'a>() -> Vec<Arg<'a, 'a>> {
+    vec![
+        Arg::from_usage("--include-tag=[TAG]... 'Outputs only the events with any of the tags'")
+            .number_of_values(1)
+            .validator(|s| {
+                s.parse::<memoparsa::Tag>()
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }),
+        Arg::from_usage(
+            "--exclude-tag=[TAG]... 'Leaves out the events with any of the tags, eg. X'",
+        )
+        .number_of_values(1)
+        .validator(|s| {
+            s.parse::<memoparsa::Tag>()
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }),
+    ]
+}
+
+/** Writes the events in the output format, or as calendars split into a directory. Returns the
+ *  exit code. */
+fn convert(matches: &ArgMatches, registry: &mut memoparsa::Registry, formats: &[&str]) -> i32 {
+    let output_file = matches.value_of("output");
+    let mut ics_config = memoparsa::IcsConfig {
+        timezone: timezone(matches),
+        ..memoparsa::IcsConfig::default()
+    };
+    if let Some(prefix) = matches.value_of("approximate-prefix") {
+        ics_config.approximate_prefix = prefix.to_owned();
+    }
+    if matches.is_present("no-tentative") {
+        ics_config.approximate_tentative = false;
+    }
+    if matches.is_present("extract-references") {
+        ics_config.extract_references = true;
+    }
+    if let Some(tags) = matches.values_of("todo-tag") {
+        ics_config.todo_policy.tags = tags.map(|tag| tag.parse().unwrap()).collect();
+    }
+    if matches.is_present("no-imprecise-todos") {
+        ics_config.todo_policy.imprecise_dates = false;
+    }
+    if let Some(categories) = matches.values_of("category") {
+        for category in categories {
+            let (from, to) = category.split_once(':').unwrap();
+            ics_config
+                .category_map
+                .insert(from.to_owned(), to.to_owned());
+        }
+    }
+    ics_config.filter = if matches.is_present("all") {
+        filter(matches, memoparsa::Filter::All)
     } else {
-        let year = Local::now().year();
-        println!(
-            "Using default year, current year in local time-zone: {}",
-            year
+        filter(matches, memoparsa::Filter::default())
+    };
+    if let Some(reminders) = matches.values_of("reminder") {
+        for reminder in reminders {
+            let (tag, offset) = parse_tag_reminder(reminder).unwrap();
+            ics_config.tag_reminders.insert(tag, offset);
+        }
+    }
+
+    registry.register_writer(Box::new(memoparsa::IcsWriter {
+        config: ics_config.clone(),
+    }));
+    let (name, events) = match read_events(matches, registry, formats, &ics_config.filter) {
+        Ok(read) => read,
+        Err(code) => return code,
+    };
+
+    if let Some(partition) = matches.value_of("split-by") {
+        let partition = match partition {
+            "tag" => memoparsa::Partition::ByTag,
+            "section" => memoparsa::Partition::BySection,
+            _ => unreachable!(),
+        };
+        let directory = output_file.unwrap();
+        if directory == "-" {
+            eprintln!("Fatal error: --split-by writes into a directory, not to stdout");
+            return exitcode::USAGE;
+        }
+        let saved = memoparsa::save_events_partitioned_as_ics(
+            &name,
+            &events,
+            &partition,
+            directory,
+            &ics_config,
         );
+        return match saved {
+            Ok(()) => exitcode::OK,
+            Err(e) => {
+                eprintln!(
+                    "Fatal error: could not save the calendars in {}: {}",
+                    directory, e
+                );
+                exitcode::IOERR
+            }
+        };
+    }
+    let writer = registry
+        .writer(matches.value_of("output-format").unwrap_or("ics"))
+        .unwrap();
+    let written = match output_file {
+        Some(output_file) if output_file != "-" => {
+            let mut file = match std::fs::File::create(output_file) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("Fatal error: could not create {}: {}", output_file, e);
+                    return exitcode::CANTCREAT;
+                }
+            };
+            writer.write(&events, &mut file)
+        }
+        _ => writer.write(&events, &mut std::io::stdout().lock()),
+    };
+    finish(written);
+    exitcode::OK
+}
+
+/** Prints the events one per line with their dates and tags, and the files they came from when
+ *  there are several. Returns the exit code. */
+fn list(matches: &ArgMatches, registry: &memoparsa::Registry, formats: &[&str]) -> i32 {
+    let (_, events) = match read_events(
+        matches,
+        registry,
+        formats,
+        &filter(matches, memoparsa::Filter::All),
+    ) {
+        Ok(read) => read,
+        Err(code) => return code,
+    };
+    let mut output = String::new();
+    for event in &events {
+        output.push_str(&row(event));
+        if let Some(file) = &event.source.file {
+            output.push_str(&format!("  {}:{}", file, event.source.span.line + 1));
+        }
+        output.push('\n');
     }
-    if let Some(input) = matches.value_of("input") {
-        println!("Selected input file: {}", input);
+    print_output(&output);
+    exitcode::OK
+}
+
+/** Prints the events of the upcoming days grouped by day, in colour on a terminal. Returns the
+ *  exit code. */
+fn agenda(matches: &ArgMatches, registry: &memoparsa::Registry, formats: &[&str]) -> i32 {
+    let days = matches.value_of("days").unwrap().parse::<usize>().unwrap();
+    let from = matches.value_of("from").map_or_else(
+        || Local::now().with_timezone(&timezone(matches)).date_naive(),
+        |from| parse_day(from).unwrap(),
+    );
+    let (_, events) = match read_events(
+        matches,
+        registry,
+        formats,
+        &filter(matches, memoparsa::Filter::All),
+    ) {
+        Ok(read) => read,
+        Err(code) => return code,
+    };
+
+    // colour only for a reader, see https://no-color.org
+    let colour = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let paint = |code: &str, text: String| match colour {
+        true => format!("\x1b[{}m{}\x1b[0m", code, text),
+        false => text,
+    };
+
+    let mut output = String::new();
+    let mut previous_day = None;
+    for occurrence in memoparsa::agenda(&events, from, days) {
+        if previous_day != Some(occurrence.day) {
+            let header = occurrence.day.format("%a %F").to_string();
+            output.push_str(&format!("{}\n", paint("1", header)));
+            previous_day = Some(occurrence.day);
+        }
+        let event = occurrence.event;
+        let tags = event.tags.iter().map(|tag| tag.key()).collect::<String>();
+        let span = match occurrence.span_day {
+            Some((day, days)) => format!(" ({}/{})", day, days),
+            None => String::new(),
+        };
+        output.push_str(&format!(
+            "    {}  {}  {}{}\n",
+            paint("36", format!("{:<11}", time(&occurrence))),
+            paint("33", format!("{:<4}", tags)),
+            event.description,
+            span
+        ));
+        // the notes of a span are shown on its first day
+        let notes = match occurrence.span_day {
+            Some((day, _)) if day > 1 => &[][..],
+            _ => &event.notes[..],
+        };
+        for note in notes {
+            let note = format!("{:<11}  {:<4}  * {}", "", "", note);
+            output.push_str(&format!("    {}\n", paint("2", note)));
+        }
+    }
+    print_output(&output);
+    exitcode::OK
+}
+
+/** Prints the diagnostics of the alpha and calendar files. Returns the exit code, which is an
+ *  error if any of the diagnostics is. */
+fn check(matches: &ArgMatches, registry: &memoparsa::Registry, formats: &[&str]) -> i32 {
+    let today = Local::now().date_naive();
+    let mut errors = false;
+    for input in matches.values_of("input").unwrap() {
+        let (path, format, context, source) = match read_file(input, matches, registry, formats) {
+            Ok(read) => read,
+            Err(code) => return code,
+        };
+        // the order of the dates is checked as written, without inferring years
+        let year = context.reference.year();
+        let diagnostics = match format.name() {
+            "alpha" => memoparsa::check_alpha(year, &source, today),
+            "calendar" => memoparsa::check_calendar(year, &source),
+            _ => Vec::new(),
+        };
+        for diagnostic in diagnostics {
+            println!("{}:{}", path, diagnostic);
+            errors |= diagnostic.severity() == memoparsa::Severity::Error;
+        }
+    }
+    if errors {
+        exitcode::DATAERR
     } else {
-        println! {"Fatal error: no input file specified"};
-        std::process::exit(exitcode::DATAERR);
+        exitcode::OK
     }
-    if let Some(in_format) = matches.value_of("format") {
-        println!("Selected input format: {}", in_format);
+}
+
+/** Prints the events removed from the old version with `-` and the ones added to the new version
+ *  with `+`, a changed event as both. Returns the exit code, which is 1 if there are changes like
+ *  for diff(1). */
+fn diff(matches: &ArgMatches, registry: &memoparsa::Registry, formats: &[&str]) -> i32 {
+    let read = |input| {
+        let (_, format, context, source) = read_file(input, matches, registry, formats)?;
+        Ok(format.parse_with_context(&context, &source).into_events())
+    };
+    let old = match read(matches.value_of("old").unwrap()) {
+        Ok(events) => events,
+        Err(code) => return code,
+    };
+    let new = match read(matches.value_of("new").unwrap()) {
+        Ok(events) => events,
+        Err(code) => return code,
+    };
+
+    let changes = memoparsa::diff(&old, &new);
+    let mut output = String::new();
+    for change in &changes {
+        match change {
+            memoparsa::Change::Removed(event) => output.push_str(&format!("- {}\n", row(event))),
+            memoparsa::Change::Added(event) => output.push_str(&format!("+ {}\n", row(event))),
+            memoparsa::Change::Changed { old, new } => {
+                output.push_str(&format!("- {}\n", row(old)));
+                output.push_str(&format!("+ {}\n", row(new)));
+            }
+        }
+    }
+    print_output(&output);
+    if changes.is_empty() {
+        exitcode::OK
     } else {
-        println! {"Fatal error: no input format specified"};
-        std::process::exit(exitcode::DATAERR);
+        1
     }
+}
+
+/** Formats the alpha files in place, or with --check lists the files that are not formatted.
+ *  Files in other formats are left out. Returns the exit code, which is a usage error if any
+ *  file was left out. */
+fn fmt(matches: &ArgMatches, registry: &memoparsa::Registry) -> i32 {
+    let year = start_year(matches);
+    let mut unformatted = false;
+    let mut skipped = false;
+    for input in matches.values_of("input").unwrap() {
+        let format = parser_for(input, None, matches, registry);
+        if format.name() != memoparsa::SourceFormat::Alpha.name() {
+            eprintln!("{}: not formatting a {} file", input, format.name());
+            skipped = true;
+            continue;
+        }
+        let source = match read_input(input) {
+            Ok(source) => source,
+            Err(e) => return could_not_read(input, e),
+        };
+        let formatted = memoparsa::format_alpha(year, &source);
+        // stdin is formatted to stdout
+        if input == "-" && !matches.is_present("check") {
+            print!("{}", formatted);
+            continue;
+        }
+        if formatted == source {
+            continue;
+        }
+        if matches.is_present("check") {
+            println!("{}", input);
+            unformatted = true;
+        } else {
+            if let Err(e) = std::fs::write(input, formatted) {
+                eprintln!("Fatal error: could not write {}: {}", input, e);
+                return exitcode::CANTCREAT;
+            }
+        }
+    }
+    if skipped {
+        exitcode::USAGE
+    } else if unformatted {
+        exitcode::DATAERR
+    } else {
+        exitcode::OK
+    }
+}
+
+/** Reads an input given as FILE[:FORMAT[:YEAR]] into its path, format, date context and
+ *  contents. The reference date is the start of the year named in the input, the date set with
+ *  --reference or the start of the start year, in that order. Returns the exit code if the file
+ *  can't be read. */
+fn read_file<'a, 'r>(
+    input: &'a str,
+    matches: &ArgMatches,
+    registry: &'r memoparsa::Registry,
+    formats: &[&str],
+) -> Result<
+    (
+        &'a str,
+        &'r dyn memoparsa::Parser,
+        memoparsa::DateContext,
+        String,
+    ),
+    i32,
+> {
+    let (path, format, year) = parse_input(input, formats);
+    let format = parser_for(path, format, matches, registry);
+    let reference = match (year, matches.value_of("reference")) {
+        (None, Some(reference)) => parse_day(reference).unwrap(),
+        (year, _) => {
+            let year = year.unwrap_or_else(|| start_year(matches));
+            NaiveDate::from_ymd_opt(year, 1, 1).unwrap()
+        }
+    };
+    let policy = matches
+        .value_of("year-policy")
+        .map_or(memoparsa::YearPolicy::Fixed, |policy| {
+            policy.parse().unwrap()
+        });
+    let context = memoparsa::DateContext {
+        timezone: timezone(matches),
+        ..memoparsa::DateContext::new(reference, policy)
+    };
+    match read_input(path) {
+        Ok(source) => Ok((path, format, context, source)),
+        Err(e) => Err(could_not_read(path, e)),
+    }
+}
+
+/** The parser of the file: the format named for it, the one set with --format, the one named in
+ *  the file name or alpha, in that order. */
+fn parser_for<'r>(
+    path: &str,
+    format: Option<String>,
+    matches: &ArgMatches,
+    registry: &'r memoparsa::Registry,
+) -> &'r dyn memoparsa::Parser {
+    format
+        .or_else(|| matches.value_of("format").map(str::to_lowercase))
+        .and_then(|format| registry.parser(&format))
+        .or_else(|| registry.detect(path))
+        .unwrap_or_else(|| registry.parser("alpha").unwrap())
+}
+
+/** Reads the events of the inputs that match the filter. The events of a single file are returned
+ *  as they are with the name of its format, and the events of several files are merged, recording
+ *  the file of each. Returns the exit code if a file can't be read. */
+fn read_events(
+    matches: &ArgMatches,
+    registry: &memoparsa::Registry,
+    formats: &[&str],
+    filter: &memoparsa::Filter,
+) -> Result<(String, Vec<memoparsa::Event>), i32> {
+    let mut files = Vec::new();
+    for input in matches.values_of("input").unwrap() {
+        let (path, format, context, source) = read_file(input, matches, registry, formats)?;
+        let events = format
+            .parse_with_context(&context, &source)
+            .into_events()
+            .into_iter()
+            .filter(|event| filter.matches(event))
+            .collect();
+        files.push((path.to_owned(), format.name().to_owned(), events));
+    }
+    if files.len() == 1 {
+        let (_, format, events) = files.remove(0);
+        Ok((format, events))
+    } else {
+        let files = files.into_iter().map(|(path, _, events)| (path, events));
+        Ok(("memoparsa".to_owned(), memoparsa::merge(files)))
+    }
+}
+
+fn start_year(matches: &ArgMatches) -> i32 {
     matches
+        .value_of("start-year")
+        .unwrap()
+        .parse::<i32>()
+        .unwrap()
 }
 
-/** Checks that the input format specifier is one of the allowed formats, eg.
- *  alpha. */
-fn validate_input_format_spec(s: String) -> Result<(), String> {
-    match s.as_ref() {
-        "alpha" | "ALPHA" => Ok(()),
-        _ => {
-            let mut msg = "unknown format: ".to_string();
-            msg.push_str(&s);
-            Err(msg)
+fn timezone(matches: &ArgMatches) -> chrono_tz::Tz {
+    matches.value_of("timezone").unwrap().parse().unwrap()
+}
+
+/** Formats an event as a line of its date, tags and description. */
+fn row(event: &memoparsa::Event) -> String {
+    let tags = event.tags.iter().map(|tag| tag.key()).collect::<String>();
+    format!(
+        "{:<26}  {:<4}  {}",
+        event.date.to_string(),
+        tags,
+        event.description
+    )
+}
+
+/** The time of an event on a day of the agenda, eg. `12:00-13:00`, only the start on the first
+ *  day of a span and the end on the last, or nothing for a whole day. */
+fn time(occurrence: &memoparsa::Occurrence) -> String {
+    let format = |time: &DateTime<FixedOffset>| time.format("%H:%M").to_string();
+    match (&occurrence.event.date, occurrence.span_day) {
+        (memoparsa::DateVariant::TimeSpan(start, _), Some((1, _))) => format!("{}-", format(start)),
+        (memoparsa::DateVariant::TimeSpan(_, end), Some((day, days))) if day == days => {
+            format!("-{}", format(end))
+        }
+        (_, Some(_)) => String::new(),
+        (memoparsa::DateVariant::TimeSpan(start, end), None) => {
+            format!("{}-{}", format(start), format(end))
         }
+        (memoparsa::DateVariant::DateTime(date), None) => format(date),
+        _ => String::new(),
     }
 }
+
+/** Parses a day given on the command line, eg. 2019-05-01 or 1.5.2019. */
+fn parse_day(s: &str) -> Option<NaiveDate> {
+    ["%F", "%d.%m.%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(s, format).ok())
+}
+
+/** Prints the output to stdout. */
+fn print_output(output: &str) {
+    use std::io::Write;
+    finish(std::io::stdout().lock().write_all(output.as_bytes()));
+}
+
+/** Fails on a write error, except when the reader of a pipeline stops early, eg.
+ *  `memoparsa list alpha.md | head`. */
+fn finish(written: std::io::Result<()>) {
+    match written {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+        written => written.unwrap(),
+    }
+}
+
+/** Splits an input into the path and the format and the start year given for it, eg.
+ *  `alpha2.md:alpha:2020`. */
+fn parse_input<'a>(input: &'a str, formats: &[&str]) -> (&'a str, Option<String>, Option<i32>) {
+    let is_format = |s: &str| formats.contains(&s.to_lowercase().as_str());
+    let mut parts = input.rsplitn(3, ':').collect::<Vec<&str>>();
+    parts.reverse();
+    match parts.as_slice() {
+        [path, format, input_year] if is_format(format) && input_year.parse::<i32>().is_ok() => {
+            (path, Some(format.to_lowercase()), input_year.parse().ok())
+        }
+        [.., format] if is_format(format) => (
+            &input[..input.len() - format.len() - 1],
+            Some(format.to_lowercase()),
+            None,
+        ),
+        _ => (input, None, None),
+    }
+}
+
+/** Reads the input file, or stdin for `-`. */
+fn read_input(path: &str) -> std::io::Result<String> {
+    if path == "-" {
+        let mut source = String::new();
+        std::io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    } else {
+        std::fs::read_to_string(path)
+    }
+}
+
+/** Prints why the input could not be read. Returns the exit code. */
+fn could_not_read(path: &str, error: std::io::Error) -> i32 {
+    eprintln!("Fatal error: could not read {}: {}", path, error);
+    exitcode::NOINPUT
+}
+
+fn owned(names: Vec<&str>) -> Vec<String> {
+    names.into_iter().map(str::to_owned).collect()
+}
+
+/** Builds the event filter from --include-tag and --exclude-tag, including the events that match
+ *  `default` when no tags are included. */
+fn filter(matches: &ArgMatches, default: memoparsa::Filter) -> memoparsa::Filter {
+    let tag_filters = |name| -> Vec<memoparsa::Filter> {
+        matches
+            .values_of(name)
+            .into_iter()
+            .flatten()
+            .map(|tag| memoparsa::Filter::Tag(tag.parse().unwrap()))
+            .collect()
+    };
+
+    let included = tag_filters("include-tag");
+    let mut filter = if included.is_empty() {
+        default
+    } else {
+        memoparsa::Filter::Or(included)
+    };
+    let excluded = tag_filters("exclude-tag");
+    if !excluded.is_empty() {
+        filter = filter.and(!memoparsa::Filter::Or(excluded));
+    }
+    filter
+}
+
+/** Parses a tag reminder specifier, eg. r:2d. */
+fn parse_tag_reminder(s: &str) -> Result<(memoparsa::Tag, chrono::Duration), String> {
+    let (tag, offset) = s
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected TAG:OFFSET, got: {}", s))?;
+    let tag = tag.parse::<memoparsa::Tag>().map_err(|e| e.to_string())?;
+    let offset =
+        memoparsa::parse_offset(offset).ok_or_else(|| format!("invalid offset: {}", offset))?;
+    Ok((tag, offset))
+}
@@ -1,5 +1,8 @@
-use ics::ICalendar;
-use memoparsa::{CreateIcsEvent, Event, Tag};
+use ics::{components::Component, ICalendar};
+use memoparsa::{
+    AlphaWriter, CreateIcsEvent, CreateIcsTodo, Document, Event, Filter, IcsConfig, IcsWriter,
+    Item, Partition, Recurrence, SourceFormat, Span, Tag, TodoPolicy, Writer,
+};
 
 #[test]
 fn alpha_parses_correct() {
@@ -7,15 +10,15 @@ fn alpha_parses_correct() {
 
     let file_contents = include_str!("../../data/alpha.md");
 
-    // split into a queue of lines
-    let lines = file_contents.split('\n');
-
-    // process lines into DOM
-    let entries = lines.filter_map(|line| Event::from_str(line, 2019).ok());
+    let document = memoparsa::parse(SourceFormat::Alpha, 2019, file_contents);
 
-    for entry in entries {
-        println!("DOM Event: {:?}", entry);
+    for (entry, lines) in document.events_with_lines() {
+        println!("DOM Event at {:?}: {:?}", lines, entry);
     }
+    for (line, text) in document.unparsed() {
+        println!("Unparsed line {}: {}", line, text);
+    }
+    assert!(document.events().count() > 0);
 }
 
 #[test]
@@ -42,3 +45,474 @@ fn alpha_converts_to_ics() {
 
     println!("ICalendar object: {:?}", calendar);
 }
+
+#[test]
+fn approximate_events_are_marked() {
+    let entry = Event::from_str("    25.05   ~        p  Stamina RPG alkaa", 2019).unwrap();
+
+    let ics = Component::from(entry.create_ics_event()).to_string();
+    assert!(ics.contains("STATUS:TENTATIVE"));
+    assert!(ics.contains("SUMMARY:[~] Stamina RPG alkaa"));
+
+    let config = IcsConfig {
+        approximate_tentative: false,
+        approximate_prefix: "(arvio) ".to_owned(),
+        ..IcsConfig::default()
+    };
+    let ics = Component::from(entry.create_ics_event_with(&config)).to_string();
+    assert!(!ics.contains("STATUS:TENTATIVE"));
+    assert!(ics.contains("SUMMARY:(arvio) Stamina RPG alkaa"));
+}
+
+#[test]
+fn referenced_dates_are_linked() {
+    let entry = Event::from_str(
+        "    29.05           p   valmistumishakemus (valm. päiv: 19.6.)",
+        2019,
+    )
+    .unwrap();
+
+    assert_eq!(entry.create_ics_events_with(&IcsConfig::default()).len(), 1);
+
+    let config = IcsConfig {
+        extract_references: true,
+        ..IcsConfig::default()
+    };
+    let events = entry
+        .create_ics_events_with(&config)
+        .into_iter()
+        .map(|event| Component::from(event).to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(events.len(), 2);
+
+    let uid = events[0]
+        .lines()
+        .find_map(|line| line.strip_prefix("UID:"))
+        .unwrap();
+    assert!(events[1].contains("DTSTART:20190619"));
+    assert!(events[1].contains("SUMMARY:valm. päiv: valmistumishakemus (valm. päiv: 19.6.)"));
+    assert!(events[1].contains(&format!("RELATED-TO:{}", uid)));
+
+    // the summary is escaped as in the event itself
+    let entry = Event::from_str("    29.05   p   kandi; työ, seminaari (DL: 19.6.)", 2019).unwrap();
+    let events = entry
+        .create_ics_events_with(&config)
+        .into_iter()
+        .map(|event| Component::from(event).to_string())
+        .collect::<Vec<String>>();
+    assert!(events[1].contains("SUMMARY:DL: kandi\\; työ\\, seminaari (DL: 19.6.)"));
+}
+
+#[test]
+fn referenced_dates_in_notes_are_linked() {
+    let source = "    25.08           p   opinnot\n    - 2.9.: TTY lukuvuosi-ilmo päättyy\n";
+    let events = memoparsa::parse(SourceFormat::Alpha, 2019, source).into_events();
+    assert_eq!(events[0].notes, vec!["2.9.: TTY lukuvuosi-ilmo päättyy"]);
+
+    let config = IcsConfig {
+        extract_references: true,
+        ..IcsConfig::default()
+    };
+    let events = events[0]
+        .create_ics_events_with(&config)
+        .into_iter()
+        .map(|event| Component::from(event).to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(events.len(), 2);
+    assert!(events[1].contains("DTSTART:20190902"));
+    assert!(events[1].contains("SUMMARY:TTY lukuvuosi-ilmo päättyy: opinnot"));
+}
+
+#[test]
+fn referenced_dates_of_todos_are_linked() {
+    let entry = Event::from_str("    29.05           H   hakemus (DL: 19.6.)", 2019).unwrap();
+    let config = IcsConfig {
+        extract_references: true,
+        ..IcsConfig::default()
+    };
+    assert!(config.todo_policy.is_todo(&entry));
+
+    let (todo, events) = entry.create_ics_todo_and_events_with(&config);
+    let todo = Component::from(todo).to_string();
+    let uid = todo
+        .lines()
+        .find_map(|line| line.strip_prefix("UID:"))
+        .unwrap();
+    assert_eq!(events.len(), 1);
+    let event = Component::from(events.into_iter().next().unwrap()).to_string();
+    assert!(event.contains("DTSTART:20190619"));
+    assert!(event.contains(&format!("RELATED-TO:{}", uid)));
+}
+
+#[test]
+fn times_are_written_in_utc() {
+    let entry = Event::from_str("    25.04   12:00-13:00   p   lounas", 2019).unwrap();
+    let ics = Component::from(entry.create_ics_event()).to_string();
+    assert!(ics.contains("DTSTART:20190425T090000Z"));
+    assert!(ics.contains("DTEND:20190425T100000Z"));
+
+    let writer = IcsWriter {
+        config: IcsConfig {
+            timezone: chrono_tz::Europe::London,
+            ..IcsConfig::default()
+        },
+    };
+    let mut out = Vec::new();
+    writer.write(&[entry], &mut out).unwrap();
+    let ics = String::from_utf8(out).unwrap();
+    assert!(ics.contains("X-WR-TIMEZONE:Europe/London"));
+    assert!(!ics.contains("VTIMEZONE"));
+}
+
+#[test]
+fn reminders_create_alarms() {
+    let entry = Event::from_str("    20.12           pr!1h   MP:n akku", 2019).unwrap();
+
+    let ics = Component::from(entry.create_ics_event()).to_string();
+    assert_eq!(ics.matches("BEGIN:VALARM").count(), 2);
+    assert!(ics.contains("ACTION:DISPLAY"));
+    assert!(ics.contains("TRIGGER:-PT1H"));
+    assert!(ics.contains("TRIGGER:-P2D"));
+
+    let config = IcsConfig {
+        tag_reminders: Default::default(),
+        ..IcsConfig::default()
+    };
+    let ics = Component::from(entry.create_ics_event_with(&config)).to_string();
+    assert_eq!(ics.matches("BEGIN:VALARM").count(), 1);
+    assert!(ics.contains("TRIGGER:-PT1H"));
+}
+
+#[test]
+fn deadlines_and_undated_entries_are_todos() {
+    let policy = TodoPolicy::default();
+
+    let deadline =
+        Event::from_str("    01.07   12:00   H   hanki suuhygienistiaika", 2019).unwrap();
+    assert!(policy.is_todo(&deadline));
+    let ics = Component::from(deadline.create_ics_todo()).to_string();
+    assert!(ics.starts_with("BEGIN:VTODO"));
+    assert!(ics.contains("DUE:20190701T"));
+    assert!(ics.contains("STATUS:NEEDS-ACTION"));
+
+    let undated = Event::from_str("    xx.07        p   expected Rytmiitti in July", 2019).unwrap();
+    assert!(policy.is_todo(&undated));
+    let ics = Component::from(undated.create_ics_todo()).to_string();
+    assert!(ics.contains("DTSTART:20190701"));
+    assert!(ics.contains("DUE:20190731"));
+
+    let appointment = Event::from_str("    03.09   12:40-13:20 p suuhygienisti", 2019).unwrap();
+    assert!(!policy.is_todo(&appointment));
+
+    let policy = TodoPolicy {
+        tags: vec![Tag::PublishToIcs],
+        imprecise_dates: false,
+    };
+    assert!(policy.is_todo(&appointment));
+    assert!(!policy.is_todo(&deadline));
+}
+
+#[test]
+fn tags_and_sections_are_categories() {
+    let mut entry =
+        Event::from_str("    14.12           Hp  lisää toistuvat kalenteriin", 2019).unwrap();
+    entry.section = Some("Annually repeating".to_owned());
+
+    let ics = Component::from(entry.create_ics_event()).to_string();
+    assert!(ics.contains("CATEGORIES:deadline,Annually repeating"));
+
+    let mut config = IcsConfig::default();
+    config
+        .category_map
+        .insert("deadline".to_owned(), "DL".to_owned());
+    config
+        .category_map
+        .insert("Annually repeating".to_owned(), "".to_owned());
+    let ics = Component::from(entry.create_ics_event_with(&config)).to_string();
+    assert!(ics.contains("CATEGORIES:DL\r"));
+}
+
+#[test]
+fn calendars_split_by_section() {
+    let source =
+        "    01.05   p   vappu\n### Repeating\n    04.06   p   tilit\n    05.06   pH  vuokra\n";
+    // a directory of its own for every run
+    let directory =
+        std::env::temp_dir().join(format!("memoparsa-split-by-section-{}", std::process::id()));
+
+    memoparsa::save_partitioned_as_ics(
+        SourceFormat::Alpha,
+        2019,
+        source,
+        &Partition::BySection,
+        &directory,
+        &IcsConfig::default(),
+    )
+    .unwrap();
+
+    let repeating = std::fs::read_to_string(directory.join("Repeating.ics")).unwrap();
+    assert!(repeating.contains("X-WR-CALNAME:Repeating"));
+    assert!(repeating.contains("PRODID:alpha/Repeating"));
+    assert!(repeating.contains("SUMMARY:tilit"));
+    assert!(repeating.contains("SUMMARY:vuokra"));
+    assert!(!repeating.contains("SUMMARY:vappu"));
+
+    // events outside of any section go to the calendar named after the format
+    let rest = std::fs::read_to_string(directory.join("alpha.ics")).unwrap();
+    assert!(rest.contains("SUMMARY:vappu"));
+
+    let config = IcsConfig::default();
+    let by_tag = Partition::Custom(Box::new(|event: &Event| {
+        event.tags.iter().map(|tag| tag.name().to_owned()).collect()
+    }));
+    memoparsa::save_partitioned_as_ics(
+        SourceFormat::Alpha,
+        2019,
+        source,
+        &by_tag,
+        &directory,
+        &config,
+    )
+    .unwrap();
+    let deadlines = std::fs::read_to_string(directory.join("deadline.ics")).unwrap();
+    assert!(deadlines.contains("SUMMARY:vuokra"));
+    assert!(!deadlines.contains("SUMMARY:tilit"));
+
+    // names that make the same file name are saved in separate files
+    let clashing = Partition::Custom(Box::new(|event: &Event| match event.tags.len() {
+        1 => vec!["a/b".to_owned()],
+        _ => vec!["a_b".to_owned()],
+    }));
+    memoparsa::save_partitioned_as_ics(
+        SourceFormat::Alpha,
+        2019,
+        source,
+        &clashing,
+        &directory,
+        &config,
+    )
+    .unwrap();
+    let first = std::fs::read_to_string(directory.join("a_b.ics")).unwrap();
+    let second = std::fs::read_to_string(directory.join("a_b-2.ics")).unwrap();
+    assert!(first.contains("X-WR-CALNAME:a/b"));
+    assert!(second.contains("X-WR-CALNAME:a_b"));
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn filters_select_parsed_events() {
+    let source = "    01.05   p   vappu\n    04.06   pX  tilit\n    05.06   vuokra\n";
+
+    assert_eq!(
+        memoparsa::parse(SourceFormat::Alpha, 2019, source)
+            .events()
+            .count(),
+        3
+    );
+
+    let filter = Filter::default().and(!Filter::Tag(Tag::CannotAttend));
+    let events = memoparsa::parse_with_filter(SourceFormat::Alpha, 2019, source, &filter);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].description, "vappu");
+}
+
+#[test]
+fn locations_notes_and_recurrences_are_written() {
+    let source = "### Repeating\n\t04.xx\tp\ttilit (@pankki)\n        - 60 € käteisellä\n";
+    let document = memoparsa::parse(SourceFormat::Alpha, 2019, source);
+    let entry = document.events().next().unwrap();
+    assert_eq!(entry.recurrence, Some(Recurrence::Monthly));
+
+    let ics = Component::from(entry.create_ics_event()).to_string();
+    assert!(ics.contains("DTSTART:20190104"));
+    assert!(ics.contains("RRULE:FREQ=MONTHLY"));
+    assert!(ics.contains("LOCATION:pankki"));
+    assert!(ics.contains("DESCRIPTION:60 € käteisellä"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn events_serialize_to_json() {
+    let source =
+        "    03.09 12:40-13:20 pr!1h suuhygienisti (@Hervanta)\n    xx.07   p   Rytmiitti\n";
+    let events = memoparsa::parse(SourceFormat::Alpha, 2019, source).into_events();
+
+    let json = serde_json::to_string(&events).unwrap();
+    assert!(json.contains("\"location\":\"Hervanta\""));
+    assert!(json.contains("\"Month\":{\"year\":2019,\"month\":7}"));
+
+    let parsed: Vec<Event> = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, events);
+}
+
+#[test]
+fn alpha_writer_round_trips() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let crafted = "\
+ALPHA-SELF {
+    25.04           p   diplomityö Janille
+ma  25.04   23:59       [](PRML kirjan laina-aika päättyy)
+    18.-19.5 11-,-19 p  staminaleiri 8 (avoin, ilmainen)
+    22.-27.10  # Rankka
+    30.12-2.1 ->    uusi vuosi
+    25.05   ~        p  Stamina RPG alkaa
+    ~14.4   12:00-13:20 pr!2d!1h  lounas (@Hervanta)
+    14.05       konvoluutioneuroverkot
+        * kysy mitä teki kandiksi
+    xx.07   p   Rytmiitti
+
+
+2020
+    1.3.2021    H   tentti
+    xx.xx       likvidiä
+
+### Repeating
+    04.xx       tilit
+}
+";
+    for source in &[
+        crafted,
+        include_str!("../../data/alpha.md"),
+        include_str!("../../data/alpha2.md"),
+    ] {
+        let document = memoparsa::parse(SourceFormat::Alpha, 2019, source);
+        let written = AlphaWriter.write_document(&document);
+        let reparsed = memoparsa::parse(SourceFormat::Alpha, 2019, &written);
+        assert_eq!(
+            without_spans(reparsed),
+            without_spans(document),
+            "written as:\n{}",
+            written
+        );
+    }
+}
+
+#[test]
+fn alpha_writer_aligns_columns() {
+    let source = "    25.04 p diplomityö\n\t02.05 12:00 p effproc\n\t05.06-07.06   r!1w vuokra\n";
+    let events = memoparsa::parse(SourceFormat::Alpha, 2019, source).into_events();
+
+    let mut out = vec![];
+    AlphaWriter.write(&events, &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "\
+2019
+    25.04           p   diplomityö
+    02.05   12:00   p   effproc
+    05.06-07.06     r!1w vuokra
+"
+    );
+}
+
+#[test]
+fn formatting_keeps_the_meaning() {
+    let source = include_str!("../../data/alpha.md");
+    let formatted = memoparsa::format_alpha(2019, source);
+
+    assert_eq!(
+        without_spans(memoparsa::parse(SourceFormat::Alpha, 2019, &formatted)),
+        without_spans(memoparsa::parse(SourceFormat::Alpha, 2019, source))
+    );
+    assert_eq!(memoparsa::format_alpha(2019, &formatted), formatted);
+
+    // lines that are not events are kept byte for byte
+    assert!(formatted.starts_with("\u{feff}ALPHA-SELF {\nViikonpäivä \n"));
+    assert!(formatted.contains("\n\tviikko 46 viikonloppuna: PP\n"));
+    assert!(formatted.contains("\nma  25.04   23:59       [](PRML"));
+    assert!(formatted.contains("\n    05.07-07.07     p   SoHu (Kotka vj)\n"));
+}
+
+#[test]
+fn formatting_keeps_blank_lines_headings_and_line_endings() {
+    let source = "    25.04 p vappu\r\n###Foo  \r\n  \r\n\t02.05 12:00 p effproc\r\n\r\n\r\n";
+
+    assert_eq!(
+        memoparsa::format_alpha(2019, source),
+        "    25.04           p   vappu\r\n###Foo  \r\n  \r\n    02.05   12:00   p   effproc\r\n\r\n\r\n"
+    );
+}
+
+/// The document without the spans of its events, which move when the columns are aligned.
+fn without_spans(mut document: Document) -> Document {
+    for block in &mut document.blocks {
+        for item in &mut block.items {
+            if let Item::Event { event, .. } = item {
+                event.source.span = Span::default();
+                event.source.date = None;
+                event.source.tags.clear();
+            }
+        }
+    }
+    document
+}
+
+#[test]
+fn merged_files_record_their_origin() {
+    let alpha = memoparsa::parse(SourceFormat::Alpha, 2019, "    01.05   p   vappu\n");
+    let alpha2 = memoparsa::parse(
+        SourceFormat::Alpha,
+        2019,
+        "    30.04       vappuaatto\n    01.05   p   vappu\n",
+    );
+    let events = memoparsa::merge(vec![
+        ("alpha.md".to_owned(), alpha.into_events()),
+        ("alpha2.md".to_owned(), alpha2.into_events()),
+    ]);
+
+    let origins = events
+        .iter()
+        .map(|event| (event.description.as_str(), event.source.file.as_deref()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        origins,
+        vec![
+            ("vappu", Some("alpha.md")),
+            ("vappuaatto", Some("alpha2.md"))
+        ]
+    );
+    let ics = events[1].create_ics_event().to_string();
+    assert!(ics.contains("X-MEMOPARSA-SOURCE:alpha2.md:1\r"));
+}
+
+#[test]
+fn check_fails_on_dates_out_of_order() {
+    let file = std::env::temp_dir().join(format!("memoparsa-check-{}.md", std::process::id()));
+    let check = |source: &str| {
+        std::fs::write(&file, source).unwrap();
+        std::process::Command::new(env!("CARGO_BIN_EXE_memoparsa"))
+            .args(["check", "-y", "2019"])
+            .arg(&file)
+            .output()
+            .unwrap()
+    };
+
+    let output = check("    04.09       tilit\n    03.09       vuokra\n");
+    assert_eq!(output.status.code(), Some(exitcode::DATAERR));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("error: 2019-09-03 is before"));
+
+    let output = check("    03.09       vuokra\n    04.09       tilit\n");
+    assert_eq!(output.status.code(), Some(exitcode::OK));
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn missing_inputs_and_unwritable_outputs_are_errors() {
+    let memoparsa = || std::process::Command::new(env!("CARGO_BIN_EXE_memoparsa"));
+    let missing = std::env::temp_dir().join(format!("memoparsa-missing-{}.md", std::process::id()));
+
+    let output = memoparsa().arg("list").arg(&missing).output().unwrap();
+    assert_eq!(output.status.code(), Some(exitcode::NOINPUT));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("could not read"));
+
+    let output = memoparsa()
+        .args(["convert", "data/alpha.md", "-o"])
+        .arg(missing.join("alpha.ics"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(exitcode::CANTCREAT));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("could not create"));
+}